@@ -0,0 +1,63 @@
+//! Compares both renderers drawing full frames through the decoded tile cache against
+//! decoding every tile row from VRAM. Run with `cargo bench --features bench`.
+
+extern crate rsgb;
+
+use rsgb::gb::component::SystemComponent;
+use rsgb::gb::gpu::{Gpu, Renderer};
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const FRAMES: u32 = 300;
+
+/// A scrolled background with the window and 40 sprites, filled with noise tiles.
+fn scene(renderer: Renderer, tile_cache: bool) -> Gpu {
+    let mut gpu = Gpu::new();
+    gpu.reset();
+    gpu.set_renderer(renderer);
+    gpu.set_tile_cache(tile_cache);
+    for addr in 0x8000..0x9800u16 {
+        gpu.write_vram(addr, (addr as usize * 37 + addr as usize / 7) as u8);
+    }
+    for addr in 0x9800..0xA000u16 {
+        gpu.write_vram(addr, (addr * 7) as u8);
+    }
+    for sprite in 0..40u16 {
+        let addr = 0xFE00 + sprite * 4;
+        gpu.write_oam(addr, 16 + (sprite as u8 * 13) % 144);
+        gpu.write_oam(addr + 1, 8 + (sprite as u8 * 29) % 160);
+        gpu.write_oam(addr + 2, sprite as u8);
+        gpu.write_oam(addr + 3, (sprite as u8 & 0x03) << 5);
+    }
+    gpu.write_register(0xFF42, 13);
+    gpu.write_register(0xFF43, 5);
+    gpu.write_register(0xFF4A, 96);
+    gpu.write_register(0xFF4B, 87);
+    // display, window map 0x9C00, window, tile data 0x8000, sprites, background
+    gpu.write_register(0xFF40, 0xF3);
+    gpu
+}
+
+fn measure(name: &str, renderer: Renderer, tile_cache: bool) -> Duration {
+    let mut gpu = scene(renderer, tile_cache);
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        while !gpu.take_frame() {
+            black_box(gpu.step(4));
+        }
+    }
+    let elapsed = start.elapsed();
+    println!("{:>16}: {:>8.1} us/frame", name, elapsed.as_secs_f64() * 1e6 / FRAMES as f64);
+    elapsed
+}
+
+fn main() {
+    for &(name, renderer) in &[("scanline", Renderer::Scanline), ("fifo", Renderer::Fifo)] {
+        let cached = measure(&format!("{} cached", name), renderer, true);
+        let decoded = measure(&format!("{} decoded", name), renderer, false);
+        println!("the cache makes the {} renderer {:.2}x as fast",
+                 name,
+                 decoded.as_secs_f64() / cached.as_secs_f64());
+    }
+}
@@ -0,0 +1,189 @@
+use gb::gpu::{Color, Gpu};
+use gb::gpu::viewer::{Image, TileMap};
+use gb::display::*;
+use gb::input::*;
+
+use sdl2::render::Renderer;
+use sdl2::render::Texture;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::keyboard::Scancode;
+use sdl2::EventPump;
+use sdl2::event::Event;
+use sdl2::Sdl;
+
+pub struct SdlDisplay<'window> {
+    pub renderer: Renderer<'window>,
+    texture: Texture,
+    #[allow(dead_code)]
+    context: Sdl,
+    last_frame_time: f64,
+    /// Seconds between the last two presented frames.
+    frame_time: Option<f64>,
+}
+
+impl<'window> SdlDisplay<'window> {
+    pub fn new(context: Sdl) -> SdlDisplay<'window> {
+        let video_subsystem = context.video().unwrap();
+
+        let window = video_subsystem.window("rsgb", 160 * 4, 144 * 4)
+            .position_centered()
+            .build()
+            .unwrap();
+        let renderer = window.renderer()
+            .accelerated()
+            .build()
+            .unwrap();
+
+        let texture = renderer.create_texture_streaming(PixelFormatEnum::RGB24, 160, 144)
+            .unwrap();
+
+        SdlDisplay {
+            renderer,
+            context,
+            texture,
+            last_frame_time: 0f64,
+            frame_time: None,
+        }
+    }
+}
+
+impl<'window> Display for SdlDisplay<'window> {
+    fn draw(&mut self, framebuffer: &[Color; SCREEN_WIDTH * SCREEN_HEIGHT]) {
+        self.update_frame_time();
+
+        let mut pixels = [0u8; SCREEN_WIDTH * SCREEN_HEIGHT * 3];
+        for (pixel, color) in pixels.chunks_mut(3).zip(framebuffer.iter()) {
+            pixel[0] = color.r;
+            pixel[1] = color.g;
+            pixel[2] = color.b;
+        }
+        self.texture.update(None, &pixels, 480).unwrap();
+
+        self.renderer.clear();
+        self.renderer.copy(&self.texture, None, None).unwrap();
+        self.renderer.present();
+    }
+}
+
+impl<'window> SdlDisplay<'window> {
+    /// Seconds between the last two presented frames, `None` before the second frame.
+    pub fn frame_time(&self) -> Option<f64> {
+        self.frame_time
+    }
+
+    fn update_frame_time(&mut self) {
+        let current = time::precise_time_s();
+        if self.last_frame_time != 0f64 {
+            self.frame_time = Some(current - self.last_frame_time);
+        }
+        self.last_frame_time = current;
+    }
+}
+
+/// A window that shows one of the GPU debug views.
+pub struct SdlViewer<'window> {
+    renderer: Renderer<'window>,
+    texture: Texture,
+}
+
+impl<'window> SdlViewer<'window> {
+    /// Opens a window at `x`, `y` for images of `width` x `height` pixels, shown `scale` times as big.
+    pub fn new(context: &Sdl,
+               title: &str,
+               (x, y): (i32, i32),
+               (width, height): (u32, u32),
+               scale: u32)
+               -> SdlViewer<'window> {
+        let video_subsystem = context.video().unwrap();
+        let window = video_subsystem.window(title, width * scale, height * scale)
+            .position(x, y)
+            .build()
+            .unwrap();
+        let renderer = window.renderer().build().unwrap();
+        let texture = renderer.create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+            .unwrap();
+
+        SdlViewer { renderer, texture }
+    }
+
+    pub fn show(&mut self, image: &Image) {
+        self.texture.update(None, &image.to_rgb(), image.width * 3).unwrap();
+        self.renderer.clear();
+        self.renderer.copy(&self.texture, None, None).unwrap();
+        self.renderer.present();
+    }
+}
+
+/// Windows for the tile sheet, both tile maps, the window map and the OAM table.
+pub struct DebugViews<'window> {
+    tiles: SdlViewer<'window>,
+    map_9800: SdlViewer<'window>,
+    map_9c00: SdlViewer<'window>,
+    window: SdlViewer<'window>,
+    oam: SdlViewer<'window>,
+}
+
+impl<'window> DebugViews<'window> {
+    pub fn new(context: &Sdl) -> DebugViews<'window> {
+        DebugViews {
+            tiles: SdlViewer::new(context, "tiles", (0, 0), (128, 192), 2),
+            map_9800: SdlViewer::new(context, "map 9800", (256, 0), (256, 256), 2),
+            map_9c00: SdlViewer::new(context, "map 9c00", (768, 0), (256, 256), 2),
+            window: SdlViewer::new(context, "window", (256, 512), (256, 256), 1),
+            oam: SdlViewer::new(context, "oam", (512, 512), (256, 360), 1),
+        }
+    }
+
+    pub fn update(&mut self, gpu: &Gpu) {
+        self.tiles.show(&gpu.tile_sheet());
+        self.map_9800.show(&gpu.tilemap_view(TileMap::Map9800));
+        self.map_9c00.show(&gpu.tilemap_view(TileMap::Map9C00));
+        self.window.show(&gpu.window_map_view());
+        self.oam.show(&gpu.oam_view());
+    }
+}
+
+/// Polls the keyboard: arrow keys, A, B, space for start and backslash for select.
+/// Escape or closing a window quits.
+pub struct SdlKeyboard {
+    event_pump: EventPump,
+    quit: bool,
+}
+
+impl SdlKeyboard {
+    pub fn new(context: Sdl) -> SdlKeyboard {
+        SdlKeyboard {
+            event_pump: context.event_pump().unwrap(),
+            quit: false,
+        }
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+}
+
+impl JoypadSource for SdlKeyboard {
+    fn poll(&mut self) -> JoypadState {
+        for event in self.event_pump.poll_iter() {
+            if let Event::Quit { .. } = event {
+                self.quit = true;
+            }
+        }
+        let state = sdl2::keyboard::KeyboardState::new(&self.event_pump);
+
+        if state.is_scancode_pressed(Scancode::Escape) {
+            self.quit = true;
+        }
+        JoypadState {
+            left: state.is_scancode_pressed(Scancode::Left),
+            right: state.is_scancode_pressed(Scancode::Right),
+            up: state.is_scancode_pressed(Scancode::Up),
+            down: state.is_scancode_pressed(Scancode::Down),
+            a: state.is_scancode_pressed(Scancode::A),
+            b: state.is_scancode_pressed(Scancode::B),
+            start: state.is_scancode_pressed(Scancode::Space),
+            select: state.is_scancode_pressed(Scancode::Backslash),
+        }
+    }
+}
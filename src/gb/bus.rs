@@ -0,0 +1,360 @@
+use gb::catridge::*;
+use gb::dma::{Dma, DMA};
+use gb::mbc::{self, Mbc};
+use gb::error::EmuError;
+use gb::gpu::Gpu;
+use gb::input::{JoypadState, RumbleCallback};
+use gb::interrupts::JOYPAD;
+use gb::interrupts::TIMER;
+use gb::timer::Timer;
+use gb::component::SystemComponent;
+
+/// Clock ticks in one m-cycle, the unit every memory access takes.
+pub const M_CYCLE: i32 = 4;
+/// Clock ticks of real time after the last write to cartridge ram until it's saved.
+#[cfg(not(test))]
+pub const SAVE_DELAY: i32 = 4_194_304;
+/// Tests use a shorter delay, so they don't have to run for a whole second.
+#[cfg(test)]
+pub const SAVE_DELAY: i32 = 0x4000;
+
+/// Owns everything the cpu talks to: the cartridge, memory, the GPU, the timer and the
+/// io registers. It also drives them, `tick` advances every component by one m-cycle.
+pub struct Bus {
+    gpu: Gpu,
+    joypad: JoypadState,
+    timer: Timer,
+    dma: Dma,
+    wram: [u8; 0x2000],
+    hram: [u8; 0x0080],
+    io: [u8; 0x0100],
+    cycles: u64,
+    frame_ready: bool,
+    interupt_enable: u8,
+    interupt_flag: u8,
+    /// KEY1 and the double speed mode only exist on the CGB.
+    cgb: bool,
+    speed_switch_armed: bool,
+    double_speed: bool,
+    mbc: Box<dyn Mbc>,
+    /// The cartridge ram keeps its contents while the power is off.
+    battery: bool,
+    /// Ticks left until the cartridge ram should be saved, counts down after each write.
+    save_countdown: Option<i32>,
+    save_due: bool,
+    /// Bytes sent over the serial port that weren't taken yet.
+    serial: Vec<u8>,
+}
+
+impl Bus {
+    pub fn new(cart: Cartrige) -> Result<Bus, EmuError> {
+        let cgb = cart.header.cgb != CgbSupport::DmgOnly;
+        let battery = cart.cartridge_type().has_battery();
+        let mbc = mbc::new(cart)?;
+        Ok(Bus {
+            wram: [0; 0x2000],
+            hram: [0; 0x0080],
+            io: [0; 0x0100],
+            gpu: Gpu::new(),
+            joypad: JoypadState::new(),
+            timer: Timer::new(),
+            dma: Dma::new(),
+            cycles: 0,
+            frame_ready: false,
+            interupt_enable: 0,
+            interupt_flag: 0,
+            cgb,
+            speed_switch_armed: false,
+            double_speed: false,
+            mbc,
+            battery,
+            save_countdown: None,
+            save_due: false,
+            serial: Vec::new(),
+        })
+    }
+
+    /// Advances all components by one m-cycle. The cpu calls this right before each of
+    /// its memory accesses, so registers like LY or STAT are up to date when they are
+    /// read partway through an instruction.
+    pub fn tick(&mut self) {
+        self.cycles += M_CYCLE as u64;
+
+        if self.timer.step(M_CYCLE) {
+            self.interupt_flag |= TIMER;
+        }
+
+        // in double speed mode the GPU and the cartridge keep running at the normal rate
+        let real_ticks = if self.double_speed { M_CYCLE / 2 } else { M_CYCLE };
+        self.interupt_flag |= self.gpu.step(real_ticks);
+        self.mbc.step(real_ticks);
+        if let Some(ticks) = self.save_countdown {
+            if ticks > real_ticks {
+                self.save_countdown = Some(ticks - real_ticks);
+            } else {
+                self.save_countdown = None;
+                self.save_due = true;
+            }
+        }
+        if self.gpu.take_frame() {
+            self.frame_ready = true;
+        }
+
+        if let Some((source, destination)) = self.dma.tick() {
+            let value = self.dma_read(source);
+            self.gpu.write_oam(destination, value);
+            self.dma.copied(value);
+        }
+    }
+
+    /// Clock ticks elapsed since the system was started.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns true once after the GPU finished drawing a frame.
+    pub fn take_frame(&mut self) -> bool {
+        let ready = self.frame_ready;
+        self.frame_ready = false;
+        ready
+    }
+
+    pub fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+
+    pub fn gpu_mut(&mut self) -> &mut Gpu {
+        &mut self.gpu
+    }
+
+    /// Updates the button state and requests the joypad interrupt for every newly pressed button.
+    pub fn set_joypad(&mut self, state: JoypadState) {
+        let released = (self.joypad.get_keys1() << 4) | self.joypad.get_keys2();
+        let pressed = !((state.get_keys1() << 4) | state.get_keys2());
+        if released & pressed != 0 {
+            self.interupt_flag |= JOYPAD;
+        }
+        self.joypad = state;
+    }
+
+    pub fn joypad(&self) -> JoypadState {
+        self.joypad
+    }
+
+    /// Performs the CGB speed switch if it was armed through KEY1, which is what STOP does
+    /// instead of entering low power mode. Returns true if the speed was switched.
+    pub fn switch_speed(&mut self) -> bool {
+        if !self.speed_switch_armed {
+            return false;
+        }
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        true
+    }
+
+    /// Lets the cartridge's real-time clock follow the host's clock instead of the
+    /// emulated cycles, so it keeps counting while the emulator isn't running.
+    pub fn use_host_clock(&mut self, enabled: bool) {
+        self.mbc.use_host_clock(enabled);
+    }
+
+    pub fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+        self.mbc.set_rumble_callback(callback);
+    }
+
+    /// Returns the bytes sent over the serial port since the last call. Test roms
+    /// report their results this way.
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.serial)
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.battery
+    }
+
+    /// Returns true once after the cartridge ram wasn't written for a while since its
+    /// last change, which is a good time to save it.
+    pub fn take_save_due(&mut self) -> bool {
+        let due = self.save_due;
+        self.save_due = false;
+        due
+    }
+
+    /// The battery backed cartridge ram in the raw `.sav` format.
+    pub fn save_data(&self) -> Vec<u8> {
+        self.mbc.save_data()
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.mbc.load_save_data(data);
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    fn read_key1(&self) -> u8 {
+        if !self.cgb {
+            return 0xFF;
+        }
+        let speed = if self.double_speed { 0x80 } else { 0 };
+        let armed = if self.speed_switch_armed { 0x01 } else { 0 };
+        0x7E | speed | armed
+    }
+
+    fn read_input(&self) -> u8 {
+        if self.io[0x00] & 0x20 == 0 {
+            // let value = 0xC0 | 15 | 0x10;
+            let value = 0xC0 | self.joypad.get_keys1() | 0x10;
+            return value;
+        }
+        if self.io[0x00] & 0x10 == 0 {
+            // let value = 0xC0 | 13 | 0x20;
+            let value = 0xC0 | self.joypad.get_keys2() | 0x20;
+            return value;
+        }
+        if self.io[0x00] & 0x30 == 0 {
+            return 0xff;
+        }
+        0
+    }
+
+    /// Reads the source of an OAM DMA transfer. DMA can't see the io registers and HRAM,
+    /// sources from 0xE000 up read the echo of work RAM.
+    fn dma_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => self.mbc.read_u8(addr),
+            0x8000..=0x9FFF => self.gpu.read_vram(addr),
+            _ => self.wram[(addr as usize - 0xC000) & 0x1FFF],
+        }
+    }
+}
+
+impl Bus {
+    /// Reads a byte without letting any time pass. Locked VRAM and OAM read as 0xFF.
+    /// While OAM DMA runs only the io registers and HRAM can be read, everything else
+    /// returns the byte the transfer is copying.
+    pub fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0xFE00..=0xFEFF if self.dma.active() => 0xFF,
+            0x0000..=0xFDFF if self.dma.active() => self.dma.value(),
+            0x0000..=0x7FFF => self.mbc.read_u8(addr),
+            0xA000..=0xBFFF => self.mbc.read_u8(addr),
+            0x8000..=0x9FFF if !self.gpu.vram_accessible() => 0xFF,
+            0x8000..=0x9FFF => self.gpu.read_vram(addr),
+            0xC000..=0xDFFF => self.wram[(addr - 0xC000) as usize],
+            0xE000..=0xFDFF => self.wram[(addr - 0xE000) as usize],
+            0xFE00..=0xFEFF if !self.gpu.oam_accessible() => 0xFF,
+            0xFE00..=0xFEFF => self.gpu.read_oam(addr),
+            0xFF04..=0xFF07 => self.timer.read_u8(addr),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.gpu.read_register(addr),
+            0xFF00 => self.read_input(),
+            DMA => self.dma.read(),
+            0xFF0F => self.interupt_flag,
+            0xFF4D => self.read_key1(),
+            // VRAM banking only exists on the CGB
+            0xFF4F if self.cgb => self.gpu.read_register(addr),
+            0xFFFF => self.interupt_enable,
+            0xFF80..=0xFFFE => self.hram[(addr - 0xff80) as usize],
+            0xFF01..=0xFF7F => self.io[(addr - 0xff00) as usize],
+        }
+    }
+
+    /// Writes a byte without letting any time pass. Writes to locked VRAM and OAM and
+    /// writes below the io registers during OAM DMA are dropped.
+    pub fn write_u8(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0xFEFF if self.dma.active() => {}
+            0x8000..=0x9FFF if !self.gpu.vram_accessible() => {}
+            0xFE00..=0xFEFF if !self.gpu.oam_accessible() => {}
+            0x0000..=0x7FFF => self.mbc.write_u8(addr, val),
+            0x8000..=0x9FFF => self.gpu.write_vram(addr, val),
+            0xA000..=0xBFFF => {
+                // writes while the ram is disabled are dropped and don't need saving
+                if self.battery && self.mbc.ram_accessible() {
+                    self.save_countdown = Some(SAVE_DELAY);
+                }
+                self.mbc.write_u8(addr, val);
+            }
+            0xC000..=0xDFFF => self.wram[(addr - 0xC000) as usize] = val,
+            0xE000..=0xFDFF => self.wram[(addr - 0xE000) as usize] = val,
+            0xFE00..=0xFEFF => self.gpu.write_oam(addr, val),
+            0xFF80..=0xFFFE => self.hram[(addr - 0xff80) as usize] = val,
+            0xFF04..=0xFF07 => self.timer.write_u8(addr, val),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.gpu.write_register(addr, val),
+            DMA => self.dma.start(val),
+            0xFF0F => self.interupt_flag = val,
+            0xFF4D => self.speed_switch_armed = self.cgb && val & 0x01 != 0,
+            0xFF4F if self.cgb => self.gpu.write_register(addr, val),
+            0xFF00..=0xFF7F => {
+                self.io[(addr - 0xff00) as usize] = val;
+                // there is no link partner, a started transfer only records the byte
+                if addr == 0xff02 && val & 0x80 != 0 {
+                    self.serial.push(self.io[1]);
+                }
+            }
+            0xFFFF => self.interupt_enable = val,
+        }
+    }
+}
+
+impl SystemComponent for Bus {
+    fn reset(&mut self) {
+        self.io[..255].copy_from_slice(&IO_RESET[..255]);
+        self.gpu.reset();
+        self.timer.reset();
+        self.dma.reset();
+        self.write_u8(0xFF05, 0);
+        self.write_u8(0xFF06, 0);
+        self.write_u8(0xFF07, 0);
+        self.write_u8(0xFF10, 0x80);
+        self.write_u8(0xFF11, 0xBF);
+        self.write_u8(0xFF12, 0xF3);
+        self.write_u8(0xFF14, 0xBF);
+        self.write_u8(0xFF16, 0x3F);
+        self.write_u8(0xFF17, 0x00);
+        self.write_u8(0xFF19, 0xBF);
+        self.write_u8(0xFF1A, 0x7A);
+        self.write_u8(0xFF1B, 0xFF);
+        self.write_u8(0xFF1C, 0x9F);
+        self.write_u8(0xFF1E, 0xBF);
+        self.write_u8(0xFF20, 0xFF);
+        self.write_u8(0xFF21, 0x00);
+        self.write_u8(0xFF22, 0x00);
+        self.write_u8(0xFF23, 0xBF);
+        self.write_u8(0xFF24, 0x77);
+        self.write_u8(0xFF25, 0xF3);
+        self.write_u8(0xFF26, 0xF1);
+        self.write_u8(0xFF40, 0x91);
+        self.write_u8(0xFF42, 0x00);
+        self.write_u8(0xFF43, 0x00);
+        self.write_u8(0xFF45, 0x00);
+        self.write_u8(0xFF47, 0xFC);
+        self.write_u8(0xFF48, 0xFF);
+        self.write_u8(0xFF49, 0xFF);
+        self.write_u8(0xFF4A, 0x00);
+        self.write_u8(0xFF4B, 0x00);
+        self.write_u8(0xFFFF, 0x00);
+
+    }
+}
+
+static IO_RESET: [u8; 0x100] =
+    [0x0F, 0x00, 0x7C, 0xFF, 0x00, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+     0x01, 0x80, 0xBF, 0xF3, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF,
+     0xBF, 0xFF, 0xFF, 0x00, 0x00, 0xBF, 0x77, 0xF3, 0xF1, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+     0xFF, 0xFF, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+     0x00, 0xFF, 0x00, 0xFF, 0x91, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFC, 0x00, 0x00, 0x00,
+     0x00, 0xFF, 0x7E, 0xFF, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x3E, 0xFF, 0xFF, 0xFF,
+     0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0,
+     0xFF, 0xC1, 0x00, 0xFE, 0xFF, 0xFF, 0xFF, 0xF8, 0xFF, 0x00, 0x00, 0x00, 0x8F, 0x00, 0x00,
+     0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00,
+     0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89,
+     0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63, 0x6E,
+     0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E, 0x45, 0xEC, 0x52, 0xFA,
+     0x08, 0xB7, 0x07, 0x5D, 0x01, 0xFD, 0xC0, 0xFF, 0x08, 0xFC, 0x00, 0xE5, 0x0B, 0xF8, 0xC2,
+     0xCE, 0xF4, 0xF9, 0x0F, 0x7F, 0x45, 0x6D, 0x3D, 0xFE, 0x46, 0x97, 0x33, 0x5E, 0x08, 0xEF,
+     0xF1, 0xFF, 0x86, 0x83, 0x24, 0x74, 0x12, 0xFC, 0x00, 0x9F, 0xB4, 0xB7, 0x06, 0xD5, 0xD0,
+     0x7A, 0x00, 0x9E, 0x04, 0x5F, 0x41, 0x2F, 0x1D, 0x77, 0x36, 0x75, 0x81, 0xAA, 0x70, 0x3A,
+     0x98, 0xD1, 0x71, 0x02, 0x4D, 0x01, 0xC1, 0xFF, 0x0D, 0x00, 0xD3, 0x05, 0xF9, 0x00, 0x0B,
+     0x00];
\ No newline at end of file
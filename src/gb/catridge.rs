@@ -1,48 +1,392 @@
+use gb::error::EmuError;
+
 use std::path::Path;
 use std::result::Result;
 use std::fs::File;
-use std::io::Error;
 use std::io::Read;
 
+/// The cartridge header ends at 0x014F, anything shorter can't be a valid rom.
+const HEADER_END: usize = 0x0150;
 
 #[derive(Debug)]
 pub struct Cartrige {
-    pub cartirge_type: CartridgeType,
+    pub header: CartridgeHeader,
     pub rom: Vec<u8>,
 }
 
 impl Cartrige {
-    pub fn from_path(path: &Path) -> Result<Cartrige, Error> {
-        let mut file = try!(File::open(path));
+    pub fn from_path(path: &Path) -> Result<Cartrige, EmuError> {
+        let mut file = File::open(path)?;
         let mut buff: Vec<u8> = Vec::new();
-        try!(file.read_to_end(&mut buff));
-        let c = Cartrige {
-            cartirge_type: CartridgeType::from_u8(buff[0x0147]),
+        file.read_to_end(&mut buff)?;
+        Cartrige::from_bytes(buff)
+    }
+
+    pub fn from_bytes(buff: Vec<u8>) -> Result<Cartrige, EmuError> {
+        let header = CartridgeHeader::parse(&buff)?;
+        if let Some(rom_size) = header.rom_size {
+            if buff.len() < rom_size {
+                return Err(EmuError::TruncatedRom {
+                    expected: rom_size,
+                    actual: buff.len(),
+                });
+            }
+        }
+        Ok(Cartrige {
+            header,
             rom: buff,
+        })
+    }
+
+    pub fn cartridge_type(&self) -> CartridgeType {
+        self.header.cartridge_type
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CgbSupport {
+    DmgOnly,
+    /// Works on DMG but uses CGB features when available (0x80).
+    Enhanced,
+    /// Only runs on a CGB (0xC0).
+    CgbOnly,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Destination {
+    Japanese,
+    Overseas,
+    Unknown(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Licensee {
+    /// Code from 0x014B.
+    Old(u8),
+    /// Two character code from 0x0144, used when the old code is 0x33.
+    New(String),
+}
+
+/// A checksum stored in the header together with the value computed from the rom.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Checksum<T> {
+    pub stored: T,
+    pub computed: T,
+}
+
+impl<T: PartialEq> Checksum<T> {
+    pub fn is_valid(&self) -> bool {
+        self.stored == self.computed
+    }
+}
+
+/// The cartridge header at 0x0100-0x014F.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    /// Four character code that newer cartridges store in the last bytes of the title area.
+    pub manufacturer_code: Option<String>,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub old_licensee: u8,
+    pub new_licensee: String,
+    pub cartridge_type: CartridgeType,
+    pub rom_size_code: u8,
+    /// Rom size in bytes, `None` if the size code is unknown.
+    pub rom_size: Option<usize>,
+    pub ram_size_code: u8,
+    /// External ram size in bytes, `None` if the size code is unknown.
+    pub ram_size: Option<usize>,
+    pub destination: Destination,
+    pub version: u8,
+    pub header_checksum: Checksum<u8>,
+    pub global_checksum: Checksum<u16>,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, EmuError> {
+        if rom.len() < HEADER_END {
+            return Err(EmuError::TruncatedRom {
+                expected: HEADER_END,
+                actual: rom.len(),
+            });
+        }
+
+        let cgb = match rom[0x0143] {
+            0xC0 => CgbSupport::CgbOnly,
+            flag if flag & 0x80 != 0 => CgbSupport::Enhanced,
+            _ => CgbSupport::DmgOnly,
+        };
+
+        // Newer cartridges shortened the title to make room for the manufacturer code and the
+        // CGB flag. Older ones use all 16 bytes for the title.
+        let manufacturer = &rom[0x013F..0x0143];
+        let has_manufacturer = cgb != CgbSupport::DmgOnly &&
+                               manufacturer.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+        let (title_end, manufacturer_code) = if has_manufacturer {
+            (0x013F, Some(ascii(manufacturer)))
+        } else if cgb != CgbSupport::DmgOnly {
+            (0x0143, None)
+        } else {
+            (0x0144, None)
         };
-        println!("Cartrige {:?}", c.cartirge_type);
-        Ok(c)
+
+        let mut header_checksum = 0u8;
+        for byte in &rom[0x0134..0x014D] {
+            header_checksum = header_checksum.wrapping_sub(*byte).wrapping_sub(1);
+        }
+
+        let mut global_checksum = 0u16;
+        for (i, byte) in rom.iter().enumerate() {
+            if i != 0x014E && i != 0x014F {
+                global_checksum = global_checksum.wrapping_add(*byte as u16);
+            }
+        }
+
+        Ok(CartridgeHeader {
+            title: ascii(&rom[0x0134..title_end]),
+            manufacturer_code,
+            cgb,
+            sgb: rom[0x0146] == 0x03,
+            old_licensee: rom[0x014B],
+            new_licensee: ascii(&rom[0x0144..0x0146]),
+            cartridge_type: CartridgeType::from_u8(rom[0x0147]),
+            rom_size_code: rom[0x0148],
+            rom_size: rom_size(rom[0x0148]),
+            ram_size_code: rom[0x0149],
+            ram_size: ram_size(rom[0x0149]),
+            destination: match rom[0x014A] {
+                0x00 => Destination::Japanese,
+                0x01 => Destination::Overseas,
+                other => Destination::Unknown(other),
+            },
+            version: rom[0x014C],
+            header_checksum: Checksum {
+                stored: rom[0x014D],
+                computed: header_checksum,
+            },
+            global_checksum: Checksum {
+                stored: ((rom[0x014E] as u16) << 8) | rom[0x014F] as u16,
+                computed: global_checksum,
+            },
+        })
+    }
+
+    pub fn licensee(&self) -> Licensee {
+        if self.old_licensee == 0x33 {
+            Licensee::New(self.new_licensee.clone())
+        } else {
+            Licensee::Old(self.old_licensee)
+        }
+    }
+
+    /// Number of 16KB rom banks.
+    pub fn rom_banks(&self) -> Option<usize> {
+        self.rom_size.map(|size| size / 0x4000)
+    }
+
+    /// Number of 8KB ram banks, the 2KB size counts as one bank.
+    pub fn ram_banks(&self) -> Option<usize> {
+        self.ram_size.map(|size| size.div_ceil(0x2000))
+    }
+
+    /// The boot rom refuses to start cartridges with a bad header checksum, the global
+    /// checksum is never verified by the hardware.
+    pub fn is_valid(&self) -> bool {
+        self.header_checksum.is_valid()
+    }
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    bytes.iter()
+        .take_while(|c| **c != 0)
+        .map(|c| if c.is_ascii_graphic() || *c == b' ' { *c as char } else { '?' })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+fn rom_size(code: u8) -> Option<usize> {
+    match code {
+        0x00..=0x08 => Some(0x8000 << code),
+        0x52 => Some(72 * 0x4000),
+        0x53 => Some(80 * 0x4000),
+        0x54 => Some(96 * 0x4000),
+        _ => None,
+    }
+}
+
+fn ram_size(code: u8) -> Option<usize> {
+    match code {
+        0x00 => Some(0),
+        0x01 => Some(0x800),
+        0x02 => Some(0x2000),
+        0x03 => Some(0x8000),
+        0x04 => Some(0x20000),
+        0x05 => Some(0x10000),
+        _ => None,
     }
 }
 
 #[derive(Debug)]
-#[derive(PartialEq)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum CartridgeType {
     Plain,
     Mbc1,
     Mbc1Ram,
-    Unknown,
+    Mbc1RamBattery,
+    Mbc2,
+    Mbc2Battery,
+    PlainRam,
+    PlainRamBattery,
+    Mmm01,
+    Mmm01Ram,
+    Mmm01RamBattery,
+    Mbc3TimerBattery,
+    Mbc3TimerRamBattery,
+    Mbc3,
+    Mbc3Ram,
+    Mbc3RamBattery,
+    Mbc5,
+    Mbc5Ram,
+    Mbc5RamBattery,
+    Mbc5Rumble,
+    Mbc5RumbleRam,
+    Mbc5RumbleRamBattery,
+    Mbc6,
+    Mbc7SensorRumbleRamBattery,
+    PocketCamera,
+    BandaiTama5,
+    HuC3,
+    HuC1RamBattery,
+    Unknown(u8),
 }
 
 impl CartridgeType {
     pub fn from_u8(value: u8) -> CartridgeType {
         match value {
-            0 => CartridgeType::Plain,
-            1 => CartridgeType::Mbc1,
-            2 => CartridgeType::Mbc1Ram,
-            _ => CartridgeType::Unknown, 
+            0x00 => CartridgeType::Plain,
+            0x01 => CartridgeType::Mbc1,
+            0x02 => CartridgeType::Mbc1Ram,
+            0x03 => CartridgeType::Mbc1RamBattery,
+            0x05 => CartridgeType::Mbc2,
+            0x06 => CartridgeType::Mbc2Battery,
+            0x08 => CartridgeType::PlainRam,
+            0x09 => CartridgeType::PlainRamBattery,
+            0x0B => CartridgeType::Mmm01,
+            0x0C => CartridgeType::Mmm01Ram,
+            0x0D => CartridgeType::Mmm01RamBattery,
+            0x0F => CartridgeType::Mbc3TimerBattery,
+            0x10 => CartridgeType::Mbc3TimerRamBattery,
+            0x11 => CartridgeType::Mbc3,
+            0x12 => CartridgeType::Mbc3Ram,
+            0x13 => CartridgeType::Mbc3RamBattery,
+            0x19 => CartridgeType::Mbc5,
+            0x1A => CartridgeType::Mbc5Ram,
+            0x1B => CartridgeType::Mbc5RamBattery,
+            0x1C => CartridgeType::Mbc5Rumble,
+            0x1D => CartridgeType::Mbc5RumbleRam,
+            0x1E => CartridgeType::Mbc5RumbleRamBattery,
+            0x20 => CartridgeType::Mbc6,
+            0x22 => CartridgeType::Mbc7SensorRumbleRamBattery,
+            0xFC => CartridgeType::PocketCamera,
+            0xFD => CartridgeType::BandaiTama5,
+            0xFE => CartridgeType::HuC3,
+            0xFF => CartridgeType::HuC1RamBattery,
+            _ => CartridgeType::Unknown(value),
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match *self {
+            CartridgeType::Plain => 0x00,
+            CartridgeType::Mbc1 => 0x01,
+            CartridgeType::Mbc1Ram => 0x02,
+            CartridgeType::Mbc1RamBattery => 0x03,
+            CartridgeType::Mbc2 => 0x05,
+            CartridgeType::Mbc2Battery => 0x06,
+            CartridgeType::PlainRam => 0x08,
+            CartridgeType::PlainRamBattery => 0x09,
+            CartridgeType::Mmm01 => 0x0B,
+            CartridgeType::Mmm01Ram => 0x0C,
+            CartridgeType::Mmm01RamBattery => 0x0D,
+            CartridgeType::Mbc3TimerBattery => 0x0F,
+            CartridgeType::Mbc3TimerRamBattery => 0x10,
+            CartridgeType::Mbc3 => 0x11,
+            CartridgeType::Mbc3Ram => 0x12,
+            CartridgeType::Mbc3RamBattery => 0x13,
+            CartridgeType::Mbc5 => 0x19,
+            CartridgeType::Mbc5Ram => 0x1A,
+            CartridgeType::Mbc5RamBattery => 0x1B,
+            CartridgeType::Mbc5Rumble => 0x1C,
+            CartridgeType::Mbc5RumbleRam => 0x1D,
+            CartridgeType::Mbc5RumbleRamBattery => 0x1E,
+            CartridgeType::Mbc6 => 0x20,
+            CartridgeType::Mbc7SensorRumbleRamBattery => 0x22,
+            CartridgeType::PocketCamera => 0xFC,
+            CartridgeType::BandaiTama5 => 0xFD,
+            CartridgeType::HuC3 => 0xFE,
+            CartridgeType::HuC1RamBattery => 0xFF,
+            CartridgeType::Unknown(value) => value,
         }
     }
+
+    pub fn has_ram(&self) -> bool {
+        matches!(*self,
+                 CartridgeType::Mbc1Ram |
+                 CartridgeType::Mbc1RamBattery |
+                 CartridgeType::Mbc2 |
+                 CartridgeType::Mbc2Battery |
+                 CartridgeType::PlainRam |
+                 CartridgeType::PlainRamBattery |
+                 CartridgeType::Mmm01Ram |
+                 CartridgeType::Mmm01RamBattery |
+                 CartridgeType::Mbc3TimerRamBattery |
+                 CartridgeType::Mbc3Ram |
+                 CartridgeType::Mbc3RamBattery |
+                 CartridgeType::Mbc5Ram |
+                 CartridgeType::Mbc5RamBattery |
+                 CartridgeType::Mbc5RumbleRam |
+                 CartridgeType::Mbc5RumbleRamBattery |
+                 CartridgeType::Mbc6 |
+                 CartridgeType::Mbc7SensorRumbleRamBattery |
+                 CartridgeType::PocketCamera |
+                 CartridgeType::HuC3 |
+                 CartridgeType::HuC1RamBattery)
+    }
+
+    pub fn has_battery(&self) -> bool {
+        matches!(*self,
+                 CartridgeType::Mbc1RamBattery |
+                 CartridgeType::Mbc2Battery |
+                 CartridgeType::PlainRamBattery |
+                 CartridgeType::Mmm01RamBattery |
+                 CartridgeType::Mbc3TimerBattery |
+                 CartridgeType::Mbc3TimerRamBattery |
+                 CartridgeType::Mbc3RamBattery |
+                 CartridgeType::Mbc5RamBattery |
+                 CartridgeType::Mbc5RumbleRamBattery |
+                 CartridgeType::Mbc6 |
+                 CartridgeType::Mbc7SensorRumbleRamBattery |
+                 CartridgeType::PocketCamera |
+                 CartridgeType::HuC3 |
+                 CartridgeType::HuC1RamBattery)
+    }
+
+    pub fn has_timer(&self) -> bool {
+        matches!(*self,
+                 CartridgeType::Mbc3TimerBattery |
+                 CartridgeType::Mbc3TimerRamBattery |
+                 CartridgeType::HuC3)
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        matches!(*self,
+                 CartridgeType::Mbc5Rumble |
+                 CartridgeType::Mbc5RumbleRam |
+                 CartridgeType::Mbc5RumbleRamBattery |
+                 CartridgeType::Mbc7SensorRumbleRamBattery)
+    }
 }
 
 
@@ -50,4 +394,81 @@ impl CartridgeType {
 fn cartrige_from_u8() {
     let ct = CartridgeType::from_u8(1);
     assert!(ct == CartridgeType::Mbc1);
-}
\ No newline at end of file
+}
+
+#[test]
+fn cartridge_type_round_trips_every_code() {
+    for code in 0..=0xFF {
+        assert_eq!(CartridgeType::from_u8(code).to_u8(), code);
+    }
+    assert_eq!(CartridgeType::from_u8(0x04), CartridgeType::Unknown(0x04));
+    assert!(CartridgeType::from_u8(0x10).has_timer());
+    assert!(CartridgeType::from_u8(0x1E).has_rumble());
+    assert!(CartridgeType::from_u8(0x1E).has_battery());
+    assert!(!CartridgeType::from_u8(0x19).has_ram());
+}
+
+#[test]
+fn cartrige_from_truncated_bytes() {
+    match Cartrige::from_bytes(vec![0; 0x100]) {
+        Err(EmuError::TruncatedRom { expected, actual }) => {
+            assert_eq!(expected, 0x150);
+            assert_eq!(actual, 0x100);
+        }
+        other => panic!("expected a truncated rom error, got {:?}", other),
+    }
+
+    // the header claims 64KB but only 32KB are present
+    let mut rom = vec![0; 0x8000];
+    rom[0x0148] = 0x01;
+    match Cartrige::from_bytes(rom) {
+        Err(EmuError::TruncatedRom { expected, actual }) => {
+            assert_eq!(expected, 0x10000);
+            assert_eq!(actual, 0x8000);
+        }
+        other => panic!("expected a truncated rom error, got {:?}", other),
+    }
+}
+
+#[test]
+fn cartridge_header_fields_and_checksums() {
+    let mut rom = vec![0; 0x10000];
+    rom[0x0134..0x013F].copy_from_slice(b"POKEMON_SLV");
+    rom[0x013F..0x0143].copy_from_slice(b"AAXE");
+    rom[0x0143] = 0x80;
+    rom[0x0144..0x0146].copy_from_slice(b"01");
+    rom[0x0146] = 0x03;
+    rom[0x0147] = 0x10;
+    rom[0x0148] = 0x01;
+    rom[0x0149] = 0x03;
+    rom[0x014A] = 0x01;
+    rom[0x014B] = 0x33;
+    rom[0x014C] = 0x02;
+    rom[0x8000] = 0xAB;
+
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    assert_eq!(header.title, "POKEMON_SLV");
+    assert_eq!(header.manufacturer_code, Some("AAXE".to_string()));
+    assert_eq!(header.cgb, CgbSupport::Enhanced);
+    assert!(header.sgb);
+    assert_eq!(header.licensee(), Licensee::New("01".to_string()));
+    assert_eq!(header.cartridge_type, CartridgeType::Mbc3TimerRamBattery);
+    assert_eq!(header.rom_banks(), Some(4));
+    assert_eq!(header.ram_banks(), Some(4));
+    assert_eq!(header.destination, Destination::Overseas);
+    assert_eq!(header.version, 0x02);
+
+    let expected: u8 = rom[0x0134..0x014D]
+        .iter()
+        .fold(0u8, |x, b| x.wrapping_sub(*b).wrapping_sub(1));
+    assert_eq!(header.header_checksum.computed, expected);
+    assert!(!header.global_checksum.is_valid());
+
+    rom[0x014D] = expected;
+    let sum = rom.iter().fold(0u16, |x, b| x.wrapping_add(*b as u16));
+    rom[0x014E] = (sum >> 8) as u8;
+    rom[0x014F] = sum as u8;
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    assert!(header.is_valid());
+    assert!(header.global_checksum.is_valid());
+}
@@ -1,75 +1,42 @@
 use gb::gpu::Color;
 
-use sdl2::render::Renderer;
-use sdl2::render::Texture;
-use sdl2::pixels::PixelFormatEnum;
-use sdl2::Sdl;
-
-use std::mem::transmute;
-
-extern crate sdl2;
-extern crate time;
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
 
+/// A sink for finished frames, e.g. a window, an image dumper or a network stream.
 pub trait Display {
-    fn draw(&mut self, framebuffer: [Color; 160 * 144]);
+    fn draw(&mut self, framebuffer: &[Color; SCREEN_WIDTH * SCREEN_HEIGHT]);
 }
 
-pub struct SdlDisplay<'window> {
-    pub renderer: Renderer<'window>,
-    texture: Texture,
-    #[allow(dead_code)]
-    context: Sdl,
-    last_frame_time: f64,
+/// Keeps a copy of the last presented frame in memory.
+pub struct FrameBuffer {
+    pub pixels: Vec<Color>,
+    pub frames: u64,
 }
 
-impl<'window> SdlDisplay<'window> {
-    pub fn new(context: Sdl) -> SdlDisplay<'window> {
-        let video_subsystem = context.video().unwrap();
-
-        let window = video_subsystem.window("rsgb", 160 * 4, 144 * 4)
-            .position_centered()
-            .build()
-            .unwrap();
-        let renderer = window.renderer()
-            .accelerated()
-            .build()
-            .unwrap();
-
-        let texture = renderer.create_texture_streaming(PixelFormatEnum::RGB24, 160, 144)
-            .unwrap();
-
-        SdlDisplay {
-            renderer: renderer,
-            context: context,
-            texture: texture,
-            last_frame_time: 0f64,
+impl FrameBuffer {
+    pub fn new() -> FrameBuffer {
+        FrameBuffer {
+            pixels: Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT),
+            frames: 0,
         }
     }
-}
 
-impl<'window> Display for SdlDisplay<'window> {
-    fn draw(&mut self, framebuffer: [Color; 160 * 144]) {
-        self.print_debug_info();
-
-        let pixels: [u8; 160 * 144 * 3] = unsafe { transmute(framebuffer) };
-        self.texture.update(None, &pixels, 480).unwrap();
-
-        self.renderer.clear();
-        self.renderer.copy(&self.texture, None, None).unwrap();
-        self.renderer.present();
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        self.pixels.get(y * SCREEN_WIDTH + x).cloned()
     }
 }
 
-impl<'window> SdlDisplay<'window> {
-    fn print_debug_info(&mut self) {
-        if self.last_frame_time == 0f64 {
-            self.last_frame_time = time::precise_time_s();
-        } else {
-            let current = time::precise_time_s();
-            let delta = current - self.last_frame_time;
-            self.last_frame_time = current;
-            println!("Frametime {}s", delta);
-        }
+impl Default for FrameBuffer {
+    fn default() -> FrameBuffer {
+        FrameBuffer::new()
+    }
+}
 
+impl Display for FrameBuffer {
+    fn draw(&mut self, framebuffer: &[Color; SCREEN_WIDTH * SCREEN_HEIGHT]) {
+        self.pixels.clear();
+        self.pixels.extend_from_slice(framebuffer);
+        self.frames += 1;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,125 @@
+use gb::component::SystemComponent;
+
+pub const DMA: u16 = 0xFF46;
+
+const OAM_START: u16 = 0xFE00;
+const LENGTH: u16 = 0xA0;
+
+/// OAM DMA. A write to 0xFF46 starts copying 160 bytes from `value * 0x100` to OAM after
+/// one m-cycle of startup delay, then one byte per m-cycle.
+pub struct Dma {
+    /// The last value written to 0xFF46.
+    register: u8,
+    /// A transfer that was requested and the m-cycles left until it starts.
+    requested: Option<(u16, u8)>,
+    source: u16,
+    /// Bytes copied by the active transfer, `None` while no transfer is running.
+    position: Option<u16>,
+    /// The byte that was copied last, it's what the cpu sees on the busy bus.
+    value: u8,
+}
+
+impl Default for Dma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dma {
+    pub fn new() -> Dma {
+        Dma {
+            register: 0xFF,
+            requested: None,
+            source: 0,
+            position: None,
+            value: 0xFF,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+
+    /// Requests a transfer from `value * 0x100`. A transfer that is already running keeps
+    /// going until the new one takes over.
+    pub fn start(&mut self, value: u8) {
+        self.register = value;
+        self.requested = Some(((value as u16) << 8, 1));
+    }
+
+    /// True while a transfer blocks the bus.
+    pub fn active(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// The byte the cpu reads from the bus while it's in use by the transfer.
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Advances by one m-cycle. Returns the source and destination of the byte that has
+    /// to be copied in this m-cycle.
+    pub fn tick(&mut self) -> Option<(u16, u16)> {
+        match self.requested {
+            Some((source, 0)) => {
+                self.requested = None;
+                self.source = source;
+                self.position = Some(0);
+            }
+            Some((source, delay)) => self.requested = Some((source, delay - 1)),
+            None => {}
+        }
+
+        let position = self.position?;
+        self.position = if position + 1 < LENGTH { Some(position + 1) } else { None };
+        Some((self.source + position, OAM_START + position))
+    }
+
+    /// Records the byte that was copied, see `value`.
+    pub fn copied(&mut self, value: u8) {
+        self.value = value;
+    }
+}
+
+impl SystemComponent for Dma {
+    fn reset(&mut self) {
+        *self = Dma::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dma: &mut Dma, cycles: usize) -> Vec<(u16, u16)> {
+        (0..cycles).filter_map(|_| dma.tick()).collect()
+    }
+
+    #[test]
+    fn transfer_starts_after_one_cycle_and_takes_160() {
+        let mut dma = Dma::new();
+        dma.start(0xC1);
+
+        assert_eq!(dma.tick(), None);
+        assert!(!dma.active());
+        assert_eq!(dma.tick(), Some((0xC100, 0xFE00)));
+        assert!(dma.active());
+        let copies = run(&mut dma, 200);
+        assert_eq!(copies.len(), 159);
+        assert_eq!(copies.last(), Some(&(0xC19F, 0xFE9F)));
+        assert!(!dma.active());
+        assert_eq!(dma.read(), 0xC1);
+    }
+
+    #[test]
+    fn restarting_keeps_the_old_transfer_running_until_the_new_one_starts() {
+        let mut dma = Dma::new();
+        dma.start(0xC0);
+        run(&mut dma, 51);
+
+        dma.start(0xD0);
+        assert_eq!(dma.tick(), Some((0xC032, 0xFE32)));
+        assert_eq!(dma.tick(), Some((0xD000, 0xFE00)));
+        assert_eq!(run(&mut dma, 200).len(), 159);
+    }
+}
@@ -0,0 +1,56 @@
+use gb::catridge::CartridgeType;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum EmuError {
+    /// The cpu fetched one of the opcodes that don't exist on the LR35902 and locked up.
+    UnknownOpcode { opcode: u8, pc: u16 },
+    /// The cartridge uses a memory bank controller that isn't emulated.
+    UnsupportedMapper(CartridgeType),
+    /// The rom file is shorter than its header requires.
+    TruncatedRom { expected: usize, actual: usize },
+    /// A line of a palette config couldn't be parsed.
+    InvalidPalette { line: usize, reason: String },
+    Io(io::Error),
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmuError::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:02X} at pc {:04X}", opcode, pc)
+            }
+            EmuError::UnsupportedMapper(ref mapper) => {
+                write!(f, "unsupported cartridge type {:?}", mapper)
+            }
+            EmuError::TruncatedRom { expected, actual } => {
+                write!(f,
+                       "rom is truncated, expected at least {} bytes but got {}",
+                       expected,
+                       actual)
+            }
+            EmuError::InvalidPalette { line, ref reason } => {
+                write!(f, "invalid palette config on line {}: {}", line, reason)
+            }
+            EmuError::Io(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for EmuError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EmuError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EmuError {
+    fn from(err: io::Error) -> EmuError {
+        EmuError::Io(err)
+    }
+}
@@ -0,0 +1,415 @@
+//! The pixel FIFO renderer. The background fetcher reads one tile row every 6 dots into
+//! the background FIFO, which shifts out one pixel per dot to the LCD. Sprites stall the
+//! output while they are fetched and get mixed in through their own FIFO.
+
+use super::{Gpu, Sprite};
+use gb::display::SCREEN_WIDTH;
+
+use std::collections::VecDeque;
+
+/// The first tile fetch of every line is thrown away.
+const DISCARDED_FETCH_DOTS: u8 = 6;
+/// Dots a sprite fetch takes once the background fetcher finished its tile.
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FetchStep {
+    Tile,
+    DataLow,
+    DataHigh,
+    /// Waits until the background FIFO is empty and pushes the 8 pixels of the tile row.
+    Push,
+}
+
+struct Fetcher {
+    step: FetchStep,
+    /// Dots spent on the current step, all steps but `Push` take two.
+    dots: u8,
+    /// Tile column relative to the left edge of the background or window.
+    tile_x: u8,
+    tile: u8,
+    /// Color numbers of the tile row that is pushed next.
+    row: [u8; 8],
+}
+
+impl Fetcher {
+    fn new() -> Fetcher {
+        Fetcher {
+            step: FetchStep::Tile,
+            dots: 0,
+            tile_x: 0,
+            tile: 0,
+            row: [0; 8],
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct ObjPixel {
+    color: u8,
+    palette: usize,
+    above_bg: bool,
+}
+
+pub struct PixelFifo {
+    /// Color numbers of the background or window pixels.
+    bg: VecDeque<u8>,
+    /// Sprite pixels lined up with `bg`, transparent ones have color 0.
+    obj: VecDeque<ObjPixel>,
+    fetcher: Fetcher,
+    /// Next pixel of the line that is sent to the LCD.
+    x: u8,
+    /// Pixels still to be dropped for the fine scroll of SCX.
+    discard: u8,
+    /// Dots before the fetcher starts working on the line.
+    stall: u8,
+    /// Sprites on the line ordered by x and the first one that wasn't fetched yet.
+    sprites: Vec<Sprite>,
+    next_sprite: usize,
+    /// Dots left of the sprite fetch in progress.
+    sprite_dots: u8,
+    /// Set once the fetcher switched over to the window on this line.
+    window: bool,
+}
+
+impl PixelFifo {
+    pub fn new() -> PixelFifo {
+        PixelFifo {
+            bg: VecDeque::with_capacity(16),
+            obj: VecDeque::with_capacity(8),
+            fetcher: Fetcher::new(),
+            x: SCREEN_WIDTH as u8,
+            discard: 0,
+            stall: 0,
+            sprites: Vec::new(),
+            next_sprite: 0,
+            sprite_dots: 0,
+            window: false,
+        }
+    }
+}
+
+impl Gpu {
+    /// Prepares the FIFO for the current line at the start of mode 3.
+    pub(super) fn start_fifo_line(&mut self) {
+        let sprites = if self.status.ob_enabled() {
+            self.scan_oam()
+        } else {
+            Vec::new()
+        };
+
+        let fifo = &mut self.fifo;
+        fifo.bg.clear();
+        fifo.obj.clear();
+        fifo.fetcher = Fetcher::new();
+        fifo.x = 0;
+        fifo.discard = self.status.scx % 8;
+        fifo.stall = DISCARDED_FETCH_DOTS;
+        fifo.sprites = sprites;
+        fifo.next_sprite = 0;
+        fifo.sprite_dots = 0;
+        fifo.window = false;
+    }
+
+    /// Advances mode 3 by one dot. Returns true once the last pixel of the line was drawn.
+    pub(super) fn fifo_dot(&mut self) -> bool {
+        if self.fifo.x as usize >= SCREEN_WIDTH {
+            return true;
+        }
+        if self.fifo.stall > 0 {
+            self.fifo.stall -= 1;
+            return false;
+        }
+        if self.fifo.sprite_dots > 0 {
+            self.fifo.sprite_dots -= 1;
+            if self.fifo.sprite_dots == 0 {
+                self.merge_sprite();
+            }
+            return false;
+        }
+        if self.sprite_pending() {
+            // the sprite fetch has to wait for the background fetcher to finish its tile
+            if self.fifo.fetcher.step == FetchStep::Push && !self.fifo.bg.is_empty() {
+                self.fifo.sprite_dots = SPRITE_FETCH_DOTS;
+            } else {
+                self.fetch_dot();
+            }
+            return false;
+        }
+
+        self.fetch_dot();
+        self.output_pixel();
+
+        if self.fifo.x as usize == SCREEN_WIDTH {
+            if self.fifo.window {
+                self.window_line += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    fn sprite_pending(&self) -> bool {
+        let x = self.fifo.x as i16;
+        self.fifo.discard == 0 &&
+        self.fifo.sprites.get(self.fifo.next_sprite).is_some_and(|sprite| sprite.x <= x)
+    }
+
+    /// Mixes the row of the next sprite into the sprite FIFO. Pixels that are already in
+    /// the FIFO belong to sprites with a higher priority and only get replaced where they
+    /// are transparent.
+    fn merge_sprite(&mut self) {
+        let pixels: Vec<ObjPixel> = {
+            let sprite = &self.fifo.sprites[self.fifo.next_sprite];
+            let height = self.sprite_height();
+            let y = self.status.ly as i16 - sprite.y;
+            // columns left of the current pixel are off screen
+            let first = self.fifo.x as i16 - sprite.x;
+            (first..8)
+                .map(|column| {
+                    ObjPixel {
+                        color: self.sprite_pixel(sprite, column, y, height),
+                        palette: sprite.palette(),
+                        above_bg: sprite.above_bg(),
+                    }
+                })
+                .collect()
+        };
+        self.fifo.next_sprite += 1;
+
+        for (i, pixel) in pixels.into_iter().enumerate() {
+            match self.fifo.obj.get_mut(i) {
+                Some(old) => {
+                    if old.color == 0 {
+                        *old = pixel;
+                    }
+                }
+                None => self.fifo.obj.push_back(pixel),
+            }
+        }
+    }
+
+    fn fetch_dot(&mut self) {
+        if self.fifo.fetcher.step == FetchStep::Push {
+            self.push_tile_row();
+            return;
+        }
+
+        self.fifo.fetcher.dots += 1;
+        if self.fifo.fetcher.dots < 2 {
+            return;
+        }
+        self.fifo.fetcher.dots = 0;
+
+        match self.fifo.fetcher.step {
+            FetchStep::Tile => {
+                self.fifo.fetcher.tile = self.vram_byte(self.fetcher_map_address());
+                self.fifo.fetcher.step = FetchStep::DataLow;
+            }
+            // the row is taken from the tile cache once both bit planes would have been
+            // read, so the data steps only keep the timing of the hardware
+            FetchStep::DataLow => self.fifo.fetcher.step = FetchStep::DataHigh,
+            FetchStep::DataHigh => {
+                let tile = self.status.tile_index(self.fifo.fetcher.tile);
+                self.fifo.fetcher.row = self.tile_row(tile, self.fetcher_tile_y() as usize % 8);
+                self.fifo.fetcher.step = FetchStep::Push;
+            }
+            FetchStep::Push => {}
+        }
+    }
+
+    fn push_tile_row(&mut self) {
+        let fifo = &mut self.fifo;
+        if !fifo.bg.is_empty() {
+            return;
+        }
+        fifo.bg.extend(fifo.fetcher.row.iter());
+        fifo.fetcher.tile_x = fifo.fetcher.tile_x.wrapping_add(1);
+        fifo.fetcher.step = FetchStep::Tile;
+    }
+
+    /// Tile map entry the fetcher reads next. SCX and SCY are read on every fetch, only
+    /// the fine scroll of SCX is latched at the start of the line.
+    fn fetcher_map_address(&self) -> u16 {
+        let tile_x = self.fifo.fetcher.tile_x as u16;
+        if self.fifo.window {
+            let y = self.window_line as u16;
+            self.status.window_tilemap() + (y / 8) * 32 + (tile_x & 31)
+        } else {
+            let y = self.status.ly.wrapping_add(self.status.scy) as u16;
+            let x = (self.status.scx as u16 / 8 + tile_x) & 31;
+            self.status.bg_tilemap() + (y / 8) * 32 + x
+        }
+    }
+
+    /// Line of the background or window the fetcher is on.
+    fn fetcher_tile_y(&self) -> u8 {
+        if self.fifo.window {
+            self.window_line
+        } else {
+            self.status.ly.wrapping_add(self.status.scy)
+        }
+    }
+
+    fn window_starts(&self) -> bool {
+        !self.fifo.window && self.fifo.discard == 0 && self.window_triggered &&
+        self.status.window_enabled() && self.fifo.x as u16 + 7 >= self.status.wx as u16
+    }
+
+    fn output_pixel(&mut self) {
+        if self.window_starts() {
+            // the window restarts the fetcher and throws away what was fetched so far
+            self.fifo.window = true;
+            self.fifo.bg.clear();
+            self.fifo.fetcher = Fetcher::new();
+            return;
+        }
+
+        let bg = match self.fifo.bg.pop_front() {
+            Some(pixel) => pixel,
+            None => return,
+        };
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return;
+        }
+        let obj = self.fifo.obj.pop_front();
+
+        // on the DMG the window is turned off together with the background
+        let bg_enabled = self.status.bg_enabled();
+        let bg = if bg_enabled { bg } else { 0 };
+        let color = match obj {
+            Some(pixel) if pixel.color != 0 && (pixel.above_bg || bg == 0) => {
+                self.sprite_palette[pixel.palette * 4 + pixel.color as usize]
+            }
+            _ if bg_enabled => self.background_palette[bg as usize],
+            _ => self.blank(),
+        };
+
+        let x = self.fifo.x as usize;
+        self.bg_line[x] = bg;
+        self.framebuffer[self.status.ly as usize * SCREEN_WIDTH + x] = color;
+        self.fifo.x += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::super::tests::set_sprite;
+
+    /// Tile 1 has a different color in every column and tile 2 is solid color 3.
+    /// The background alternates between them, the window only uses tile 1.
+    fn scene(renderer: Renderer) -> Gpu {
+        let mut gpu = Gpu::new();
+        gpu.reset();
+        gpu.set_renderer(renderer);
+        gpu.write_register(0xFF47, 0xE4);
+        gpu.write_register(0xFF48, 0xE4);
+        gpu.write_register(0xFF49, 0x1B);
+        for row in 0..8 {
+            gpu.write_vram(0x8010 + row * 2, 0x55);
+            gpu.write_vram(0x8011 + row * 2, 0x33);
+            gpu.write_vram(0x8020 + row * 2, 0xFF);
+            gpu.write_vram(0x8021 + row * 2, 0xFF);
+        }
+        for addr in 0x9800..0x9C00 {
+            gpu.write_vram(addr, (addr % 3) as u8);
+        }
+        for addr in 0x9C00..0xA000 {
+            gpu.write_vram(addr, 0x01);
+        }
+        gpu
+    }
+
+    fn render_frame(gpu: &mut Gpu) {
+        while !gpu.take_frame() {
+            gpu.step(4);
+        }
+    }
+
+    /// Steps to the start of line 0 and returns how many dots mode 3 of that line took.
+    fn mode_3_length(gpu: &mut Gpu) -> usize {
+        render_frame(gpu);
+        while gpu.status.ly != 0 {
+            gpu.step(4);
+        }
+        gpu.step(OAM_SCAN_DOTS as i32);
+        let mut dots = 0;
+        while gpu.read_register(0xFF41) & 0x03 == 3 {
+            gpu.step(1);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn matches_the_scanline_renderer_for_static_frames() {
+        let mut frames = Vec::new();
+        let setups = [(Renderer::Scanline, true),
+                      (Renderer::Fifo, true),
+                      (Renderer::Scanline, false),
+                      (Renderer::Fifo, false)];
+        for &(renderer, tile_cache) in &setups {
+            let mut gpu = scene(renderer);
+            gpu.set_tile_cache(tile_cache);
+            gpu.write_register(0xFF42, 5);
+            gpu.write_register(0xFF43, 3);
+            gpu.write_register(0xFF4A, 40);
+            gpu.write_register(0xFF4B, 7 + 100);
+            set_sprite(&mut gpu, 0, 10, 0, 1, 0x00);
+            set_sprite(&mut gpu, 1, 14, 4, 2, 0x90);
+            set_sprite(&mut gpu, 2, 14, 2, 1, 0x30);
+            set_sprite(&mut gpu, 3, 0, 50, 1, 0x00);
+            gpu.write_register(0xFF40, 0xF3);
+            render_frame(&mut gpu);
+            render_frame(&mut gpu);
+            frames.push(gpu.framebuffer.iter().map(|color| color.r).collect::<Vec<u8>>());
+        }
+
+        assert!(frames.iter().all(|frame| *frame == frames[0]));
+    }
+
+    #[test]
+    fn mode_3_gets_longer_with_fine_scroll_window_and_sprites() {
+        let mut gpu = scene(Renderer::Fifo);
+        gpu.write_register(0xFF40, 0x83);
+        assert_eq!(mode_3_length(&mut gpu), 172);
+
+        gpu.write_register(0xFF43, 3);
+        assert_eq!(mode_3_length(&mut gpu), 175);
+
+        gpu.write_register(0xFF43, 0);
+        set_sprite(&mut gpu, 0, 40, 0, 1, 0);
+        assert!(mode_3_length(&mut gpu) > 172);
+
+        set_sprite(&mut gpu, 0, 0, 100, 1, 0);
+        gpu.write_register(0xFF4A, 0);
+        gpu.write_register(0xFF4B, 7 + 80);
+        gpu.write_register(0xFF40, 0xA3);
+        assert!(mode_3_length(&mut gpu) > 172);
+    }
+
+    #[test]
+    fn palette_writes_during_mode_3_split_the_line() {
+        let mut gpu = scene(Renderer::Fifo);
+        for addr in 0x9800..0x9C00 {
+            gpu.write_vram(addr, 0x02);
+        }
+        gpu.write_register(0xFF40, 0x91);
+        render_frame(&mut gpu);
+        while gpu.status.ly != 0 {
+            gpu.step(4);
+        }
+
+        // 12 dots of fetching before the first pixel, then 80 pixels
+        gpu.step(OAM_SCAN_DOTS as i32 + 12 + 80);
+        gpu.write_register(0xFF47, 0x00);
+        gpu.step(LINE_DOTS as i32);
+
+        assert_eq!(gpu.framebuffer[0].r, 0);
+        assert_eq!(gpu.framebuffer[79].r, 0);
+        assert_eq!(gpu.framebuffer[80].r, 255);
+        assert_eq!(gpu.framebuffer[159].r, 255);
+    }
+}
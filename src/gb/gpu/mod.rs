@@ -0,0 +1,992 @@
+use gb::component::SystemComponent;
+use gb::interrupts::*;
+use gb::display::SCREEN_WIDTH;
+use gb::palette::DmgPalette;
+
+use std::mem;
+
+mod fifo;
+pub mod tiles;
+pub mod viewer;
+
+use self::fifo::PixelFifo;
+use self::tiles::{TileCache, BANKS, BANK_SIZE};
+
+/// Dots of the OAM scan (mode 2) at the start of every visible line.
+const OAM_SCAN_DOTS: u16 = 80;
+/// Length of mode 3 with the scanline renderer. It's the shortest mode 3 can get.
+const SCANLINE_DRAW_DOTS: u16 = 172;
+const LINE_DOTS: u16 = 456;
+
+/// How the GPU turns VRAM into pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Renderer {
+    /// Draws a whole line at the end of a fixed length mode 3. Fast, but register writes
+    /// during mode 3 only show up on the next line.
+    Scanline,
+    /// Emulates the pixel FIFO and the fetchers one dot at a time. Mode 3 gets longer with
+    /// SCX fine scrolling, the window and sprites, and mid-line register writes take effect
+    /// on the pixel they happen at.
+    Fifo,
+}
+
+#[allow(dead_code)]
+pub struct Status {
+    pub lcdc: u8,
+    pub stat: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    lyc: u8,
+    dma: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    vbk: u8,
+    bgpi: u8,
+    obpi: u8,
+}
+
+#[allow(dead_code)]
+impl Status {
+    fn new() -> Status {
+        Status {
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            dma: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            vbk: 0,
+            bgpi: 0,
+            obpi: 0,
+        }
+    }
+
+    fn display_enabled(&self) -> bool {
+        self.lcdc & (0x01 << 7) > 1
+    }
+    fn window_tilemap(&self) -> u16 {
+        if self.lcdc & (0x01 << 6) != 0 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+    fn window_enabled(&self) -> bool {
+        self.lcdc & (0x01 << 5) > 1
+    }
+
+    fn bg_tile_data(&self) -> u16 {
+        if self.lcdc & (0x01 << 4) > 1 {
+            0x8000
+        } else {
+            0x8800
+        }
+    }
+    /// Address of a background or window tile. In 0x8800 mode the tile index is signed
+    /// and relative to 0x9000.
+    fn tile_address(&self, tile: u8) -> u16 {
+        if self.bg_tile_data() == 0x8000 {
+            0x8000 + tile as u16 * 16
+        } else {
+            (0x9000 + (tile as i8 as i32) * 16) as u16
+        }
+    }
+
+    /// Index of a background or window tile in the tile cache.
+    fn tile_index(&self, tile: u8) -> usize {
+        (self.tile_address(tile) - 0x8000) as usize / 16
+    }
+
+    fn bg_tilemap(&self) -> u16 {
+        if self.lcdc & (0x01 << 3) > 1 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+
+    fn ob_size(&self) -> bool {
+        self.lcdc & (0x01 << 2) > 0
+    }
+    fn ob_enabled(&self) -> bool {
+        self.lcdc & (0x01 << 1) > 0
+    }
+    fn bg_enabled(&self) -> bool {
+        self.lcdc & (0x01 << 0) == 1
+    }
+}
+
+pub struct Gpu {
+    mode: GpuMode,
+    /// Dots since the start of the current line.
+    dot: u16,
+    /// The first line after the LCD is turned on starts in mode 0 and skips the OAM scan.
+    first_line: bool,
+    renderer: Renderer,
+    fifo: PixelFifo,
+    /// Blocks CPU access to VRAM and OAM while the GPU is using them.
+    access_locks: bool,
+    /// Colors the shades of BGP, OBP0 and OBP1 map to.
+    palette: DmgPalette,
+    background_palette: [Color; 4],
+    sprite_palette: [Color; 8],
+    pub framebuffer: [Color; 160 * 144],
+    frame_ready: bool,
+    /// Interrupts that were requested but not handed to the bus yet.
+    interrupts: u8,
+    stat_line: bool,
+    /// Set once LY matched WY during the current frame, the window can only show up after that.
+    window_triggered: bool,
+    /// Line of the window that is drawn next. Only advances on lines the window was drawn on.
+    window_line: u8,
+    /// Color numbers of the background and window pixels on the current line,
+    /// sprites that are behind the background only show through color 0.
+    bg_line: [u8; SCREEN_WIDTH],
+    tiles: TileCache,
+    /// Decodes tiles from VRAM on every access instead of using `tiles`.
+    #[cfg(any(test, feature = "bench"))]
+    decode_vram: bool,
+    /// Both VRAM banks, the second one is only reachable on the CGB through VBK.
+    vram: [u8; BANK_SIZE * BANKS],
+    oam: [u8; 0xA0],
+    pub status: Status,
+}
+
+enum GpuMode {
+    HBlank,
+    VBlank,
+    Oam,
+    Vram,
+}
+
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+static WHITE: Color = Color {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+impl Default for Gpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gpu {
+    pub fn new() -> Gpu {
+        Gpu {
+            mode: GpuMode::HBlank,
+            dot: 0,
+            first_line: false,
+            renderer: Renderer::Fifo,
+            fifo: PixelFifo::new(),
+            access_locks: false,
+            palette: DmgPalette::default(),
+            background_palette: [WHITE; 4],
+            sprite_palette: [WHITE; 8],
+            framebuffer: [WHITE; 160 * 144],
+            frame_ready: false,
+            interrupts: 0,
+            stat_line: false,
+            window_triggered: false,
+            window_line: 0,
+            bg_line: [0; SCREEN_WIDTH],
+            tiles: TileCache::new(),
+            #[cfg(any(test, feature = "bench"))]
+            decode_vram: false,
+            vram: [0; BANK_SIZE * BANKS],
+            oam: [0; 0xA0],
+            status: Status::new(),
+        }
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        self.renderer
+    }
+
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    /// Turning the tile cache off makes both renderers decode every tile row from VRAM
+    /// again. It's only there to measure what the cache saves.
+    #[cfg(any(test, feature = "bench"))]
+    pub fn set_tile_cache(&mut self, enabled: bool) {
+        self.decode_vram = !enabled;
+    }
+
+    /// Enables the VRAM and OAM locks, they are off by default.
+    pub fn set_access_locks(&mut self, enabled: bool) {
+        self.access_locks = enabled;
+    }
+
+    /// True if the CPU can access VRAM, it's locked during mode 3.
+    pub fn vram_accessible(&self) -> bool {
+        match self.mode {
+            GpuMode::Vram => !self.access_locks,
+            _ => true,
+        }
+    }
+
+    /// True if the CPU can access OAM, it's locked during modes 2 and 3.
+    pub fn oam_accessible(&self) -> bool {
+        match self.mode {
+            GpuMode::Oam | GpuMode::Vram => !self.access_locks,
+            _ => true,
+        }
+    }
+
+    /// Advances the GPU by `ticks` clock ticks.
+    /// Returns the interrupts that were requested.
+    pub fn step(&mut self, ticks: i32) -> u8 {
+        if self.status.display_enabled() {
+            for _ in 0..ticks {
+                self.step_dot();
+            }
+            self.update_stat_line();
+        }
+        mem::replace(&mut self.interrupts, 0)
+    }
+
+    fn step_dot(&mut self) {
+        self.dot += 1;
+
+        match self.mode {
+            GpuMode::HBlank => {
+                if self.first_line && self.dot == OAM_SCAN_DOTS {
+                    self.first_line = false;
+                    self.start_drawing();
+                } else if self.dot == LINE_DOTS {
+                    self.dot = 0;
+                    self.status.ly += 1;
+                    if self.status.ly == 144 {
+                        self.mode = GpuMode::VBlank;
+                        self.frame_ready = true;
+                        self.interrupts |= VBLANK;
+                    } else {
+                        self.mode = GpuMode::Oam;
+                    }
+                }
+            }
+            GpuMode::VBlank => {
+                if self.dot == LINE_DOTS {
+                    self.dot = 0;
+                    self.status.ly += 1;
+                    if self.status.ly > 153 {
+                        self.status.ly = 0;
+                        self.window_triggered = false;
+                        self.window_line = 0;
+                        self.clear_framebuffer();
+                        self.mode = GpuMode::Oam
+                    }
+                }
+            }
+            GpuMode::Oam => {
+                if self.dot == OAM_SCAN_DOTS {
+                    self.start_drawing();
+                }
+            }
+            GpuMode::Vram => {
+                let done = match self.renderer {
+                    Renderer::Scanline => {
+                        let done = self.dot >= OAM_SCAN_DOTS + SCANLINE_DRAW_DOTS;
+                        if done {
+                            self.render_scanline();
+                        }
+                        done
+                    }
+                    Renderer::Fifo => self.fifo_dot(),
+                };
+                if done {
+                    self.mode = GpuMode::HBlank;
+                }
+            }
+        }
+    }
+
+    fn start_drawing(&mut self) {
+        self.mode = GpuMode::Vram;
+        if self.status.ly == self.status.wy {
+            self.window_triggered = true;
+        }
+        if self.renderer == Renderer::Fifo {
+            self.start_fifo_line();
+        }
+    }
+
+    /// LY is held at 0 in mode 0 while the LCD is off and the screen stays blank.
+    fn turn_off(&mut self) {
+        self.mode = GpuMode::HBlank;
+        self.dot = 0;
+        self.first_line = false;
+        self.status.ly = 0;
+        self.window_triggered = false;
+        self.window_line = 0;
+        self.stat_line = false;
+        self.clear_framebuffer();
+    }
+
+    fn turn_on(&mut self) {
+        self.mode = GpuMode::HBlank;
+        self.dot = 0;
+        self.first_line = true;
+        self.update_stat_line();
+    }
+
+    fn mode_bits(&self) -> u8 {
+        match self.mode {
+            GpuMode::HBlank => 0,
+            GpuMode::VBlank => 1,
+            GpuMode::Oam => 2,
+            GpuMode::Vram => 3,
+        }
+    }
+
+    fn read_stat(&self) -> u8 {
+        let coincidence = if self.status.ly == self.status.lyc { 0x04 } else { 0 };
+        0x80 | (self.status.stat & 0x78) | coincidence | self.mode_bits()
+    }
+
+    /// The STAT interrupt is requested on the rising edge of all enabled sources ORed
+    /// together. While one source holds the line high the others can't trigger another
+    /// interrupt ("STAT blocking").
+    fn update_stat_line(&mut self) {
+        let stat = self.status.stat;
+        let mode = match self.mode {
+            GpuMode::HBlank => stat & 0x08,
+            GpuMode::VBlank => stat & 0x10,
+            GpuMode::Oam => stat & 0x20,
+            GpuMode::Vram => 0,
+        };
+        let coincidence = if self.status.ly == self.status.lyc { stat & 0x40 } else { 0 };
+
+        let line = self.status.display_enabled() && mode | coincidence != 0;
+        if line && !self.stat_line {
+            self.interrupts |= LCDSTAT;
+        }
+        self.stat_line = line;
+    }
+
+    fn vram_offset(&self, addr: u16) -> usize {
+        (self.status.vbk & 0x01) as usize * BANK_SIZE + (addr - 0x8000) as usize
+    }
+
+    /// Reads from the VRAM bank selected by VBK.
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        self.vram[self.vram_offset(addr)]
+    }
+
+    /// Writes to the VRAM bank selected by VBK.
+    pub fn write_vram(&mut self, addr: u16, val: u8) {
+        let offset = self.vram_offset(addr);
+        self.vram[offset] = val;
+        self.tiles.update(&self.vram, offset);
+    }
+
+    /// Reads from VRAM bank 0, which holds everything the DMG renderers use.
+    fn vram_byte(&self, addr: u16) -> u8 {
+        self.vram[(addr - 0x8000) as usize]
+    }
+
+    pub fn tiles(&self) -> &TileCache {
+        &self.tiles
+    }
+
+    /// Color numbers of row `y` of `tile`, every renderer and viewer decodes tiles
+    /// through this.
+    fn tile_row(&self, tile: usize, y: usize) -> [u8; 8] {
+        #[cfg(any(test, feature = "bench"))]
+        {
+            if self.decode_vram {
+                return tiles::decode_tile_row(&self.vram, tile, y);
+            }
+        }
+        *self.tiles.row(tile, y)
+    }
+
+    pub fn read_oam(&self, addr: u16) -> u8 {
+        self.oam.get((addr - 0xFE00) as usize).cloned().unwrap_or(0xFF)
+    }
+
+    pub fn write_oam(&mut self, addr: u16, val: u8) {
+        if let Some(byte) = self.oam.get_mut((addr - 0xFE00) as usize) {
+            *byte = val;
+        }
+    }
+
+    pub fn read_register(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF40 => self.status.lcdc,
+            0xFF41 => self.read_stat(),
+            0xFF42 => self.status.scy,
+            0xFF43 => self.status.scx,
+            0xFF44 => self.status.ly,
+            0xFF45 => self.status.lyc,
+            0xFF47 => self.status.bgp,
+            0xFF48 => self.status.obp0,
+            0xFF49 => self.status.obp1,
+            0xFF4A => self.status.wy,
+            0xFF4B => self.status.wx,
+            0xFF4F => 0xFE | self.status.vbk,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0xFF40 => {
+                let was_enabled = self.status.display_enabled();
+                self.status.lcdc = val;
+                match (was_enabled, self.status.display_enabled()) {
+                    (true, false) => self.turn_off(),
+                    (false, true) => self.turn_on(),
+                    _ => {}
+                }
+            }
+            0xFF41 => {
+                self.status.stat = val & 0x78;
+                self.update_stat_line();
+            }
+            0xFF42 => self.status.scy = val,
+            0xFF43 => self.status.scx = val,
+            0xFF45 => {
+                self.status.lyc = val;
+                self.update_stat_line();
+            }
+            0xFF47 => self.update_background_palette(val),
+            0xFF48 => self.update_sprite_palette(0, val),
+            0xFF49 => self.update_sprite_palette(1, val),
+            0xFF4A => self.status.wy = val,
+            0xFF4B => self.status.wx = val,
+            0xFF4F => self.status.vbk = val & 0x01,
+            _ => {}
+        }
+    }
+
+    fn render_scanline(&mut self) {
+        self.bg_line = [0; SCREEN_WIDTH];
+        // on the DMG the window is turned off together with the background
+        if self.status.bg_enabled() {
+            self.render_background();
+            if self.status.window_enabled() {
+                self.render_window();
+            }
+        }
+        if self.status.ob_enabled() {
+            self.render_sprites();
+        }
+    }
+
+    /// Returns true once after the GPU finished drawing a frame.
+    pub fn take_frame(&mut self) -> bool {
+        let ready = self.frame_ready;
+        self.frame_ready = false;
+        ready
+    }
+
+    pub fn clear_framebuffer(&mut self) {
+        self.framebuffer = [self.blank(); 160 * 144];
+    }
+
+    fn render_window(&mut self) {
+        let start = self.status.wx as i32 - 7;
+        if !self.window_triggered || start >= SCREEN_WIDTH as i32 {
+            return;
+        }
+
+        let map = self.status.window_tilemap();
+        let line = self.status.ly as usize * SCREEN_WIDTH;
+        let y = self.window_line;
+        for x in start.max(0)..SCREEN_WIDTH as i32 {
+            let pixel = self.tile_pixel(map, (x - start) as u8, y);
+            self.bg_line[x as usize] = pixel;
+            self.framebuffer[line + x as usize] = self.background_palette[pixel as usize];
+        }
+        self.window_line += 1;
+    }
+
+    fn render_background(&mut self) {
+        let map = self.status.bg_tilemap();
+        let line = self.status.ly as usize * SCREEN_WIDTH;
+        let y = self.status.ly.wrapping_add(self.status.scy);
+        for x in 0..SCREEN_WIDTH {
+            let pixel = self.tile_pixel(map, (x as u8).wrapping_add(self.status.scx), y);
+            self.bg_line[x] = pixel;
+            self.framebuffer[line + x] = self.background_palette[pixel as usize];
+        }
+    }
+
+    /// Color number (0-3) of the pixel at `x`, `y` of the 256x256 pixel tile map at `map`.
+    fn tile_pixel(&self, map: u16, x: u8, y: u8) -> u8 {
+        let tile = self.vram_byte(map + (y as u16 / 8) * 32 + x as u16 / 8);
+        self.tile_row(self.status.tile_index(tile), (y % 8) as usize)[(x % 8) as usize]
+    }
+
+    /// OAM scan, only the first 10 sprites on the line in OAM order are drawn.
+    /// They are returned ordered by x, the sprite with the lower x coordinate wins and
+    /// the sort is stable so ties go to the one that comes first in OAM.
+    fn scan_oam(&self) -> Vec<Sprite> {
+        const MAX_SPRITES_PER_LINE: usize = 10;
+        let height = self.sprite_height();
+        let ly = self.status.ly as i16;
+
+        let mut sprites: Vec<Sprite> = (0..40)
+            .map(|i| Sprite::from_index(&self.oam, i))
+            .filter(|sprite| sprite.y <= ly && ly < sprite.y + height)
+            .take(MAX_SPRITES_PER_LINE)
+            .collect();
+        sprites.sort_by_key(|sprite| sprite.x);
+        sprites
+    }
+
+    fn sprite_height(&self) -> i16 {
+        if self.status.ob_size() { 16 } else { 8 }
+    }
+
+    fn render_sprites(&mut self) {
+        let height = self.sprite_height();
+        let ly = self.status.ly as i16;
+        let sprites = self.scan_oam();
+
+        let line = ly as usize * SCREEN_WIDTH;
+        for x in 0..SCREEN_WIDTH as i16 {
+            let hit = sprites.iter()
+                .filter(|sprite| sprite.x <= x && x < sprite.x + 8)
+                .map(|sprite| (sprite, self.sprite_pixel(sprite, x - sprite.x, ly - sprite.y, height)))
+                .find(|&(_, color)| color != 0);
+
+            if let Some((sprite, color)) = hit {
+                if sprite.above_bg() || self.bg_line[x as usize] == 0 {
+                    let palette_offset = sprite.palette() * 4;
+                    self.framebuffer[line + x as usize] =
+                        self.sprite_palette[palette_offset + color as usize];
+                }
+            }
+        }
+    }
+
+    /// Color number of the pixel at `x`, `y` relative to the top left corner of `sprite`.
+    fn sprite_pixel(&self, sprite: &Sprite, x: i16, y: i16, height: i16) -> u8 {
+        let x = if sprite.flip_x() { 7 - x } else { x };
+        let y = if sprite.flip_y() { height - 1 - y } else { y };
+        // tall sprites ignore the lowest bit of the tile number
+        let tile = if height == 16 {
+            (sprite.tile_number & 0xFE) as usize + (y / 8) as usize
+        } else {
+            sprite.tile_number as usize
+        };
+        self.tile_row(tile, (y % 8) as usize)[x as usize]
+    }
+
+    /// The color of the screen while nothing is drawn.
+    fn blank(&self) -> Color {
+        self.palette.bg[0]
+    }
+
+    pub fn palette(&self) -> DmgPalette {
+        self.palette
+    }
+
+    /// Switches the colors, the current palette registers are mapped again right away.
+    pub fn set_palette(&mut self, palette: DmgPalette) {
+        self.palette = palette;
+        let (bgp, obp0, obp1) = (self.status.bgp, self.status.obp0, self.status.obp1);
+        self.update_background_palette(bgp);
+        self.update_sprite_palette(0, obp0);
+        self.update_sprite_palette(1, obp1);
+    }
+
+    pub fn update_background_palette(&mut self, val: u8) {
+        self.status.bgp = val;
+        for i in 0..4 {
+            let index = ((val >> (i * 2)) & 3) as usize;
+            self.background_palette[i] = self.palette.bg[index];
+        }
+    }
+    pub fn update_sprite_palette(&mut self, index: usize, val: u8) {
+        if index == 0 {
+            self.status.obp0 = val;
+        } else {
+            self.status.obp1 = val;
+        }
+        let shades = if index == 0 { self.palette.obj0 } else { self.palette.obj1 };
+        for i in 0..4 {
+            let palette = ((val >> (i * 2)) & 3) as usize;
+            self.sprite_palette[index * 4 + i] = shades[palette];
+        }
+    }
+}
+
+impl SystemComponent for Gpu {
+    fn reset(&mut self) {
+        self.background_palette = self.palette.bg;
+        self.sprite_palette[..4].copy_from_slice(&self.palette.obj0);
+        self.sprite_palette[4..].copy_from_slice(&self.palette.obj1);
+    }
+}
+
+struct Sprite {
+    x: i16,
+    y: i16,
+    tile_number: u8,
+    options: u8,
+}
+
+impl Sprite {
+    fn from_index(oam: &[u8; 0xA0], index: usize) -> Sprite {
+        const SPRITE_SIZE: usize = 4;
+        let sprite_y = oam[index * SPRITE_SIZE] as i16 - 16;
+        let sprite_x = oam[index * SPRITE_SIZE + 1] as i16 - 8;
+        let sprite_tilenumber = oam[index * SPRITE_SIZE + 2];
+        let sprite_options = oam[index * SPRITE_SIZE + 3];
+
+        Sprite {
+            x: sprite_x,
+            y: sprite_y,
+            tile_number: sprite_tilenumber,
+            options: sprite_options,
+        }
+
+    }
+
+    fn above_bg(&self) -> bool {
+        self.options & (0x01 << 7) == 0
+    }
+
+    /// Horizontal flip, mirrors the columns of the sprite.
+    fn flip_x(&self) -> bool {
+        self.options & 0x20 == 0x20
+    }
+
+    /// Vertical flip, mirrors the rows of the sprite.
+    fn flip_y(&self) -> bool {
+        self.options & 0x40 == 0x40
+    }
+
+    fn palette(&self) -> usize {
+        if self.options & 0x10 != 0 { 1 } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A GPU at the start of a frame with tile 1 in the window map and tile 0 in the
+    /// background map. Row `dark_row` of tile 1 is black, everything else is white.
+    fn window_gpu(dark_row: u16) -> Gpu {
+        let mut gpu = Gpu::new();
+        gpu.reset();
+        gpu.write_register(0xFF47, 0xE4);
+        gpu.write_vram(0x8010 + dark_row * 2, 0xFF);
+        gpu.write_vram(0x8011 + dark_row * 2, 0xFF);
+        for addr in 0x9C00..0xA000 {
+            gpu.write_vram(addr, 0x01);
+        }
+        // display, window map 0x9C00, window, tile data 0x8000, background
+        gpu.write_register(0xFF40, 0xF1);
+        start_frame(&mut gpu);
+        gpu
+    }
+
+    fn start_frame(gpu: &mut Gpu) {
+        while !gpu.take_frame() {
+            gpu.step(4);
+        }
+        step_until_line(gpu, 0);
+    }
+
+    fn step_until_line(gpu: &mut Gpu, ly: u8) {
+        while gpu.status.ly != ly {
+            gpu.step(4);
+        }
+    }
+
+    fn is_black(gpu: &Gpu, x: usize, y: usize) -> bool {
+        gpu.framebuffer[y * SCREEN_WIDTH + x].r == 0
+    }
+
+    #[test]
+    fn window_is_drawn_from_wx_and_wy() {
+        let mut gpu = window_gpu(0);
+        gpu.write_register(0xFF4A, 2);
+        gpu.write_register(0xFF4B, 7 + 80);
+        step_until_line(&mut gpu, 4);
+
+        assert!(!is_black(&gpu, 100, 1));
+        assert!(!is_black(&gpu, 79, 2));
+        assert!(is_black(&gpu, 80, 2));
+        assert!(is_black(&gpu, 159, 2));
+        assert!(!is_black(&gpu, 100, 3));
+    }
+
+    #[test]
+    fn window_line_only_advances_when_the_window_is_drawn() {
+        let mut gpu = window_gpu(1);
+        gpu.write_register(0xFF4A, 0);
+        gpu.write_register(0xFF4B, 7);
+        step_until_line(&mut gpu, 1);
+        // moving the window off screen for a line pauses its line counter
+        gpu.write_register(0xFF4B, 200);
+        step_until_line(&mut gpu, 2);
+        gpu.write_register(0xFF4B, 7);
+        step_until_line(&mut gpu, 3);
+
+        assert!(!is_black(&gpu, 0, 0));
+        assert!(!is_black(&gpu, 0, 1));
+        assert!(is_black(&gpu, 0, 2));
+    }
+
+    #[test]
+    fn window_waits_for_ly_to_match_wy() {
+        let mut gpu = window_gpu(0);
+        gpu.write_register(0xFF4A, 10);
+        gpu.write_register(0xFF4B, 7);
+        step_until_line(&mut gpu, 5);
+        // WY is moved to a line that already passed, so the window stays hidden
+        gpu.write_register(0xFF4A, 2);
+        step_until_line(&mut gpu, 8);
+
+        assert!(!is_black(&gpu, 0, 6));
+        assert!(!is_black(&gpu, 0, 7));
+    }
+
+    /// Background tile 0 is blank, tile 1 is solid color 3, tile 2 only has its left
+    /// column set and tile 5 is solid color 3 while tile 4 is blank.
+    fn sprite_gpu(lcdc: u8) -> Gpu {
+        let mut gpu = Gpu::new();
+        gpu.reset();
+        gpu.write_register(0xFF47, 0xE4);
+        // color 3 is black in OBP0 and light gray in OBP1
+        gpu.write_register(0xFF48, 0xE4);
+        gpu.write_register(0xFF49, 0x40);
+        for row in 0..8 {
+            gpu.write_vram(0x8010 + row * 2, 0xFF);
+            gpu.write_vram(0x8011 + row * 2, 0xFF);
+            gpu.write_vram(0x8020 + row * 2, 0x80);
+            gpu.write_vram(0x8021 + row * 2, 0x80);
+            gpu.write_vram(0x8050 + row * 2, 0xFF);
+            gpu.write_vram(0x8051 + row * 2, 0xFF);
+        }
+        gpu.write_register(0xFF40, lcdc);
+        gpu
+    }
+
+    /// Places sprite `index` at screen position `x`, `y`.
+    pub(super) fn set_sprite(gpu: &mut Gpu, index: u16, x: u8, y: u8, tile: u8, options: u8) {
+        let addr = 0xFE00 + index * 4;
+        gpu.write_oam(addr, y + 16);
+        gpu.write_oam(addr + 1, x + 8);
+        gpu.write_oam(addr + 2, tile);
+        gpu.write_oam(addr + 3, options);
+    }
+
+    fn render_line(gpu: &mut Gpu, ly: u8) {
+        gpu.status.ly = ly;
+        gpu.render_scanline();
+    }
+
+    fn shade(gpu: &Gpu, x: usize, y: usize) -> u8 {
+        gpu.framebuffer[y * SCREEN_WIDTH + x].r
+    }
+
+    #[test]
+    fn only_ten_sprites_per_line() {
+        let mut gpu = sprite_gpu(0x93);
+        for i in 0..11 {
+            set_sprite(&mut gpu, i, i as u8 * 8, 0, 1, 0);
+        }
+        render_line(&mut gpu, 0);
+
+        assert_eq!(shade(&gpu, 72, 0), 0);
+        assert_eq!(shade(&gpu, 80, 0), 255);
+    }
+
+    #[test]
+    fn tall_sprites_ignore_the_lowest_tile_bit() {
+        let mut gpu = sprite_gpu(0x97);
+        set_sprite(&mut gpu, 0, 0, 0, 5, 0);
+        for ly in 0..17 {
+            render_line(&mut gpu, ly);
+        }
+
+        assert_eq!(shade(&gpu, 0, 7), 255);
+        assert_eq!(shade(&gpu, 0, 8), 0);
+        assert_eq!(shade(&gpu, 0, 15), 0);
+        assert_eq!(shade(&gpu, 0, 16), 255);
+    }
+
+    #[test]
+    fn lower_x_wins_then_lower_oam_index() {
+        let mut gpu = sprite_gpu(0x93);
+        set_sprite(&mut gpu, 0, 4, 0, 1, 0x10);
+        set_sprite(&mut gpu, 1, 0, 0, 1, 0x00);
+        set_sprite(&mut gpu, 2, 20, 0, 1, 0x10);
+        set_sprite(&mut gpu, 3, 20, 0, 1, 0x00);
+        render_line(&mut gpu, 0);
+
+        assert_eq!(shade(&gpu, 6, 0), 0);
+        assert_eq!(shade(&gpu, 10, 0), 192);
+        assert_eq!(shade(&gpu, 20, 0), 192);
+    }
+
+    #[test]
+    fn background_priority_only_hides_sprites_behind_colors_1_to_3() {
+        let mut gpu = sprite_gpu(0x93);
+        // the background is solid on the left half of the first tile row
+        for addr in 0x9800..0x9802 {
+            gpu.write_vram(addr, 0x01);
+        }
+        set_sprite(&mut gpu, 0, 12, 0, 1, 0x90);
+        render_line(&mut gpu, 0);
+
+        // still the background at x < 16, the sprite on top of color 0 after that
+        assert_eq!(shade(&gpu, 12, 0), 0);
+        assert_eq!(shade(&gpu, 16, 0), 192);
+    }
+
+    #[test]
+    fn horizontal_and_vertical_flip() {
+        let mut gpu = sprite_gpu(0x97);
+        set_sprite(&mut gpu, 0, 0, 0, 2, 0x20);
+        set_sprite(&mut gpu, 1, 16, 0, 5, 0x40);
+        for ly in 0..16 {
+            render_line(&mut gpu, ly);
+        }
+
+        assert_eq!(shade(&gpu, 0, 0), 255);
+        assert_eq!(shade(&gpu, 7, 0), 0);
+        assert_eq!(shade(&gpu, 16, 0), 0);
+        assert_eq!(shade(&gpu, 16, 8), 255);
+    }
+
+    #[test]
+    fn palettes_map_the_current_registers_through_the_preset() {
+        use gb::palette::{GREEN, GREY, POCKET};
+
+        let mut gpu = sprite_gpu(0x93);
+        set_sprite(&mut gpu, 0, 0, 0, 1, 0x10);
+        gpu.set_palette(DmgPalette {
+            bg: GREEN,
+            obj0: GREY,
+            obj1: POCKET,
+        });
+        render_line(&mut gpu, 0);
+
+        assert_eq!(gpu.framebuffer[0], POCKET[1]);
+        assert_eq!(gpu.framebuffer[8], GREEN[0]);
+    }
+
+    /// Steps the GPU by `ticks` and counts the STAT interrupts it requested.
+    fn count_stat_interrupts(gpu: &mut Gpu, ticks: i32) -> usize {
+        (0..ticks / 4).filter(|_| gpu.step(4) & LCDSTAT != 0).count()
+    }
+
+    fn enabled_gpu() -> Gpu {
+        let mut gpu = Gpu::new();
+        gpu.write_register(0xFF40, 0x80);
+        gpu
+    }
+
+    #[test]
+    fn stat_reports_mode_and_coincidence() {
+        let mut gpu = enabled_gpu();
+        start_frame(&mut gpu);
+        gpu.write_register(0xFF45, 0);
+        gpu.write_register(0xFF41, 0xFF);
+
+        assert_eq!(gpu.read_register(0xFF41), 0xFE);
+        gpu.step(80);
+        assert_eq!(gpu.read_register(0xFF41) & 0x07, 0x07);
+        gpu.step(172);
+        assert_eq!(gpu.read_register(0xFF41) & 0x07, 0x04);
+        gpu.step(204);
+        assert_eq!(gpu.read_register(0xFF41) & 0x07, 0x02);
+    }
+
+    #[test]
+    fn lyc_requests_the_stat_interrupt() {
+        let mut gpu = enabled_gpu();
+        start_frame(&mut gpu);
+        gpu.write_register(0xFF45, 5);
+        gpu.write_register(0xFF41, 0x40);
+
+        assert_eq!(count_stat_interrupts(&mut gpu, 456 * 5 - 4), 0);
+        assert_eq!(count_stat_interrupts(&mut gpu, 4), 1);
+        assert_eq!(count_stat_interrupts(&mut gpu, 456), 0);
+    }
+
+    #[test]
+    fn overlapping_stat_sources_block_each_other() {
+        let mut gpu = enabled_gpu();
+        start_frame(&mut gpu);
+        gpu.write_register(0xFF45, 1);
+        gpu.write_register(0xFF41, 0x48);
+
+        // the HBlank of line 0 holds the line high when LY becomes 1, which then keeps
+        // it high until the end of line 1
+        assert_eq!(count_stat_interrupts(&mut gpu, 456 * 2), 1);
+        assert_eq!(count_stat_interrupts(&mut gpu, 456), 1);
+    }
+
+    #[test]
+    fn vblank_starts_at_line_144() {
+        let mut gpu = enabled_gpu();
+        start_frame(&mut gpu);
+        while gpu.step(4) & VBLANK == 0 {}
+
+        assert_eq!(gpu.status.ly, 144);
+        assert_eq!(gpu.read_register(0xFF41) & 0x03, 1);
+    }
+
+    #[test]
+    fn lcd_off_holds_ly_at_0_in_mode_0_and_blanks_the_screen() {
+        let mut gpu = window_gpu(0);
+        gpu.write_register(0xFF4B, 7);
+        step_until_line(&mut gpu, 5);
+        assert!(is_black(&gpu, 0, 0));
+
+        gpu.write_register(0xFF40, 0x71);
+        let mut interrupts = 0;
+        for _ in 0..70224 / 4 {
+            interrupts |= gpu.step(4);
+        }
+
+        assert_eq!(interrupts, 0);
+        assert_eq!(gpu.read_register(0xFF44), 0);
+        assert_eq!(gpu.read_register(0xFF41) & 0x03, 0);
+        assert!(!is_black(&gpu, 0, 0));
+    }
+
+    #[test]
+    fn first_line_after_turning_the_lcd_on_skips_the_oam_scan() {
+        let mut gpu = enabled_gpu();
+        gpu.write_register(0xFF41, 0x20);
+
+        assert_eq!(count_stat_interrupts(&mut gpu, 76), 0);
+        assert_eq!(gpu.read_register(0xFF41) & 0x03, 0);
+        gpu.step(4);
+        assert_eq!(gpu.read_register(0xFF41) & 0x03, 3);
+        // the next line starts with a regular OAM scan
+        assert_eq!(count_stat_interrupts(&mut gpu, 456 - 80), 1);
+        assert_eq!(gpu.read_register(0xFF41) & 0x03, 2);
+    }
+}
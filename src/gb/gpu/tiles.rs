@@ -0,0 +1,100 @@
+//! Tile data decoded into color numbers. The cache is updated on every write to the tile
+//! data area, so the renderers never have to combine the two bit planes themselves.
+
+/// Size of one VRAM bank.
+pub const BANK_SIZE: usize = 0x2000;
+/// Tiles in the tile data area (0x8000-0x97FF) of one VRAM bank.
+pub const TILES_PER_BANK: usize = 384;
+/// VRAM banks on the CGB, the DMG only has the first one.
+pub const BANKS: usize = 2;
+
+const TILE_SIZE: usize = 16;
+
+/// Color numbers (0-3) of every pixel of every tile in both VRAM banks. Tiles of the
+/// second bank come after the 384 tiles of the first one.
+pub struct TileCache {
+    tiles: Box<[[[u8; 8]; 8]]>,
+}
+
+impl Default for TileCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TileCache {
+    pub fn new() -> TileCache {
+        TileCache { tiles: vec![[[0; 8]; 8]; TILES_PER_BANK * BANKS].into_boxed_slice() }
+    }
+
+    /// Decodes the tile row that contains the byte at `offset` again. `vram` holds both
+    /// banks and `offset` is relative to its start.
+    pub fn update(&mut self, vram: &[u8], offset: usize) {
+        let bank = offset / BANK_SIZE;
+        let offset = offset % BANK_SIZE;
+        if offset >= TILES_PER_BANK * TILE_SIZE {
+            return;
+        }
+
+        let row = bank * BANK_SIZE + (offset & !1);
+        let tile = bank * TILES_PER_BANK + offset / TILE_SIZE;
+        self.tiles[tile][(offset / 2) % 8] = decode_row(vram[row], vram[row + 1]);
+    }
+
+    /// Color numbers of row `y` of `tile`.
+    pub fn row(&self, tile: usize, y: usize) -> &[u8; 8] {
+        &self.tiles[tile][y]
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+/// Color numbers of a row of 8 pixels stored as its low and high bit plane.
+pub fn decode_row(low: u8, high: u8) -> [u8; 8] {
+    let mut row = [0; 8];
+    for (x, pixel) in row.iter_mut().enumerate() {
+        let bit = 7 - x;
+        *pixel = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+    }
+    row
+}
+
+/// Decodes row `y` of `tile` straight from VRAM without the cache.
+pub fn decode_tile_row(vram: &[u8], tile: usize, y: usize) -> [u8; 8] {
+    let bank = tile / TILES_PER_BANK;
+    let row = bank * BANK_SIZE + (tile % TILES_PER_BANK) * TILE_SIZE + y * 2;
+    decode_row(vram[row], vram[row + 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_matches_vram_in_both_banks() {
+        let mut vram = vec![0; BANK_SIZE * BANKS];
+        let mut cache = TileCache::new();
+        for offset in 0..vram.len() {
+            vram[offset] = (offset * 37 + offset / 5) as u8;
+            cache.update(&vram, offset);
+        }
+
+        assert_eq!(cache.len(), 768);
+        for tile in 0..cache.len() {
+            for y in 0..8 {
+                assert_eq!(*cache.row(tile, y), decode_tile_row(&vram, tile, y));
+            }
+        }
+    }
+
+    #[test]
+    fn rows_are_decoded_from_both_bit_planes() {
+        assert_eq!(decode_row(0b1010_0000, 0b1100_0001), [3, 2, 1, 0, 0, 0, 0, 2]);
+    }
+}
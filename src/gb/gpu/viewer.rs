@@ -0,0 +1,306 @@
+//! Debug views of the GPU state: the tile sheet, the tile maps and the OAM table. They
+//! are plain images, so frontends can show them in a window or export them as PNG.
+
+use super::{Color, Gpu, Sprite};
+use gb::display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use gb::gpu::tiles::TILES_PER_BANK;
+use gb::png;
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+const SHEET_COLUMNS: usize = 16;
+const MAP_SIZE: usize = 256;
+const VIEWPORT: Color = Color { r: 255, g: 0, b: 0 };
+const PAPER: Color = Color { r: 255, g: 255, b: 255 };
+const INK: Color = Color { r: 0, g: 0, b: 0 };
+/// Shown where a sprite is transparent.
+const TRANSPARENT: Color = Color { r: 0xD0, g: 0xD0, b: 0xD0 };
+
+/// 3x5 pixel glyphs of the hex digits, the lowest 3 bits of every row are used.
+const GLYPHS: [[u8; 5]; 16] = [[7, 5, 5, 5, 7],
+                               [2, 6, 2, 2, 7],
+                               [7, 1, 7, 4, 7],
+                               [7, 1, 7, 1, 7],
+                               [5, 5, 7, 1, 1],
+                               [7, 4, 7, 1, 7],
+                               [7, 4, 7, 5, 7],
+                               [7, 1, 1, 1, 1],
+                               [7, 5, 7, 5, 7],
+                               [7, 5, 7, 1, 7],
+                               [2, 5, 7, 5, 5],
+                               [6, 5, 6, 5, 6],
+                               [7, 4, 4, 4, 7],
+                               [6, 5, 5, 5, 6],
+                               [7, 4, 7, 4, 7],
+                               [7, 4, 7, 4, 4]];
+const GLYPH_SCALE: usize = 2;
+const DIGIT_ADVANCE: usize = 4 * GLYPH_SCALE;
+/// The OAM table has two columns of 20 sprites.
+const OAM_ROWS: usize = 20;
+const OAM_ROW_HEIGHT: usize = 18;
+const OAM_COLUMN_WIDTH: usize = 128;
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, color: Color) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Three bytes per pixel, row by row.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|color| vec![color.r, color.g, color.b]).collect()
+    }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        png::write(path, self.width as u32, self.height as u32, &self.to_rgb())
+    }
+
+    fn draw_hex(&mut self, x: usize, y: usize, value: u8) {
+        for (digit, &nibble) in [value >> 4, value & 0x0F].iter().enumerate() {
+            let glyph = &GLYPHS[nibble as usize];
+            for row in 0..5 * GLYPH_SCALE {
+                for column in 0..3 * GLYPH_SCALE {
+                    if glyph[row / GLYPH_SCALE] & (4 >> (column / GLYPH_SCALE)) != 0 {
+                        self.set(x + digit * DIGIT_ADVANCE + column, y + row, INK);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TileMap {
+    Map9800,
+    Map9C00,
+}
+
+impl TileMap {
+    fn address(self) -> u16 {
+        match self {
+            TileMap::Map9800 => 0x9800,
+            TileMap::Map9C00 => 0x9C00,
+        }
+    }
+}
+
+/// One sprite as it's stored in OAM.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OamEntry {
+    pub index: usize,
+    pub x: u8,
+    pub y: u8,
+    pub tile: u8,
+    pub attributes: u8,
+}
+
+impl fmt::Display for OamEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{:2}: x {:02X} y {:02X} tile {:02X} attributes {:02X}",
+               self.index,
+               self.x,
+               self.y,
+               self.tile,
+               self.attributes)
+    }
+}
+
+impl Gpu {
+    /// All 384 tiles of VRAM bank 0, 16 per row, colored with BGP.
+    pub fn tile_sheet(&self) -> Image {
+        let rows = TILES_PER_BANK / SHEET_COLUMNS;
+        let mut image = Image::new(SHEET_COLUMNS * 8, rows * 8, PAPER);
+        for tile in 0..TILES_PER_BANK {
+            let (left, top) = ((tile % SHEET_COLUMNS) * 8, (tile / SHEET_COLUMNS) * 8);
+            for y in 0..8 {
+                for (x, &pixel) in self.tile_row(tile, y).iter().enumerate() {
+                    image.set(left + x, top + y, self.background_palette[pixel as usize]);
+                }
+            }
+        }
+        image
+    }
+
+    /// The whole 256x256 pixel tile map using the current tile data area. If it's the
+    /// map the background is drawn from, the part that is on screen is outlined.
+    pub fn tilemap_view(&self, map: TileMap) -> Image {
+        let mut image = self.map_image(map.address());
+        if map.address() == self.status.bg_tilemap() {
+            self.draw_viewport(&mut image);
+        }
+        image
+    }
+
+    /// The tile map the window is drawn from.
+    pub fn window_map_view(&self) -> Image {
+        self.map_image(self.status.window_tilemap())
+    }
+
+    fn map_image(&self, map: u16) -> Image {
+        let mut image = Image::new(MAP_SIZE, MAP_SIZE, PAPER);
+        for y in 0..MAP_SIZE {
+            for x in 0..MAP_SIZE {
+                let pixel = self.tile_pixel(map, x as u8, y as u8);
+                image.set(x, y, self.background_palette[pixel as usize]);
+            }
+        }
+        image
+    }
+
+    /// Outlines the screen at SCX/SCY, wrapping around the edges of the map.
+    fn draw_viewport(&self, image: &mut Image) {
+        let (left, top) = (self.status.scx as usize, self.status.scy as usize);
+        let (right, bottom) = (left + SCREEN_WIDTH - 1, top + SCREEN_HEIGHT - 1);
+        for x in left..=right {
+            image.set(x % MAP_SIZE, top, VIEWPORT);
+            image.set(x % MAP_SIZE, bottom % MAP_SIZE, VIEWPORT);
+        }
+        for y in top..=bottom {
+            image.set(left, y % MAP_SIZE, VIEWPORT);
+            image.set(right % MAP_SIZE, y % MAP_SIZE, VIEWPORT);
+        }
+    }
+
+    pub fn oam_entries(&self) -> Vec<OamEntry> {
+        self.oam
+            .chunks(4)
+            .enumerate()
+            .map(|(index, bytes)| {
+                OamEntry {
+                    index,
+                    y: bytes[0],
+                    x: bytes[1],
+                    tile: bytes[2],
+                    attributes: bytes[3],
+                }
+            })
+            .collect()
+    }
+
+    /// A table of all 40 sprites: the OAM index, the sprite itself drawn with its
+    /// palette, then X, Y, the tile number and the attributes in hex.
+    pub fn oam_view(&self) -> Image {
+        let columns = 40 / OAM_ROWS;
+        let mut image = Image::new(OAM_COLUMN_WIDTH * columns, OAM_ROW_HEIGHT * OAM_ROWS, PAPER);
+        let height = self.sprite_height();
+
+        for entry in self.oam_entries() {
+            let left = (entry.index / OAM_ROWS) * OAM_COLUMN_WIDTH;
+            let top = (entry.index % OAM_ROWS) * OAM_ROW_HEIGHT;
+            image.draw_hex(left + 2, top + 4, entry.index as u8);
+
+            let sprite = Sprite::from_index(&self.oam, entry.index);
+            for y in 0..height {
+                for x in 0..8 {
+                    let color = match self.sprite_pixel(&sprite, x, y, height) {
+                        0 => TRANSPARENT,
+                        pixel => self.sprite_palette[sprite.palette() * 4 + pixel as usize],
+                    };
+                    image.set(left + 20 + x as usize, top + 1 + y as usize, color);
+                }
+            }
+
+            for (field, &value) in [entry.x, entry.y, entry.tile, entry.attributes].iter().enumerate() {
+                image.draw_hex(left + 32 + field * 24, top + 4, value);
+            }
+        }
+        image
+    }
+}
+
+/// Writes every view as a PNG into `dir`: tiles.png, map_9800.png, map_9c00.png,
+/// window.png and oam.png.
+pub fn export(gpu: &Gpu, dir: &Path) -> io::Result<()> {
+    gpu.tile_sheet().write_png(&dir.join("tiles.png"))?;
+    gpu.tilemap_view(TileMap::Map9800).write_png(&dir.join("map_9800.png"))?;
+    gpu.tilemap_view(TileMap::Map9C00).write_png(&dir.join("map_9c00.png"))?;
+    gpu.window_map_view().write_png(&dir.join("window.png"))?;
+    gpu.oam_view().write_png(&dir.join("oam.png"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gb::component::SystemComponent;
+
+    fn gpu() -> Gpu {
+        let mut gpu = Gpu::new();
+        gpu.reset();
+        gpu.write_register(0xFF47, 0xE4);
+        gpu.write_register(0xFF48, 0xE4);
+        // tile 1 is solid black
+        for addr in 0x8010..0x8020 {
+            gpu.write_vram(addr, 0xFF);
+        }
+        gpu
+    }
+
+    #[test]
+    fn tile_sheet_has_16_tiles_per_row() {
+        let sheet = gpu().tile_sheet();
+
+        assert_eq!((sheet.width, sheet.height), (128, 192));
+        assert_eq!(sheet.pixel(7, 7).r, 255);
+        assert_eq!(sheet.pixel(8, 0).r, 0);
+        assert_eq!(sheet.pixel(15, 7).r, 0);
+        assert_eq!(sheet.pixel(16, 0).r, 255);
+    }
+
+    #[test]
+    fn background_map_outlines_the_viewport() {
+        let mut gpu = gpu();
+        gpu.write_register(0xFF40, 0x91);
+        gpu.write_register(0xFF42, 200);
+        gpu.write_register(0xFF43, 100);
+        gpu.write_vram(0x9C00, 0x01);
+
+        let map = gpu.tilemap_view(TileMap::Map9800);
+        assert_eq!(map.pixel(100, 200), VIEWPORT);
+        assert_eq!(map.pixel(259 - 256, 200), VIEWPORT);
+        assert_eq!(map.pixel(100, 343 - 256), VIEWPORT);
+        assert_eq!(map.pixel(101, 201), PAPER);
+
+        let other = gpu.tilemap_view(TileMap::Map9C00);
+        assert_eq!(other.pixel(100, 200), PAPER);
+        assert_eq!(other.pixel(0, 0).r, 0);
+    }
+
+    #[test]
+    fn oam_table_lists_every_sprite() {
+        let mut gpu = gpu();
+        gpu.write_oam(0xFE04, 0x20);
+        gpu.write_oam(0xFE05, 0x18);
+        gpu.write_oam(0xFE06, 0x01);
+        gpu.write_oam(0xFE07, 0x80);
+
+        let entries = gpu.oam_entries();
+        assert_eq!(entries.len(), 40);
+        assert_eq!(entries[1].to_string(), " 1: x 18 y 20 tile 01 attributes 80");
+
+        let table = gpu.oam_view();
+        assert_eq!((table.width, table.height), (256, 360));
+        // sprite 1 is drawn in the second row
+        assert_eq!(table.pixel(20, 19), INK);
+        assert_eq!(table.pixel(20, 1), TRANSPARENT);
+    }
+}
@@ -1,47 +1,64 @@
-use sdl2::*;
-use sdl2::keyboard::Scancode;
-extern crate sdl2;
-pub struct Input {
-    event_pump: EventPump,
-    a: bool,
-    b: bool,
-    start: bool,
-    select: bool,
-    left: bool,
-    right: bool,
-    up: bool,
-    down: bool,
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Key {
+    A,
+    B,
+    Start,
+    Select,
+    Left,
+    Right,
+    Up,
+    Down,
 }
 
-impl Input {
-    pub fn new(context: Sdl) -> Input {
-        let event_pump = context.event_pump().unwrap();
-        Input {
-            event_pump: event_pump,
-            a: false,
-            b: false,
-            start: false,
-            select: false,
-            left: false,
-            right: false,
-            up: false,
-            down: false,
+/// Button state of the joypad, `true` meaning pressed.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct JoypadState {
+    pub a: bool,
+    pub b: bool,
+    pub start: bool,
+    pub select: bool,
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+}
+
+impl JoypadState {
+    pub fn new() -> JoypadState {
+        JoypadState::default()
+    }
+
+    pub fn set_key(&mut self, key: Key, pressed: bool) {
+        match key {
+            Key::A => self.a = pressed,
+            Key::B => self.b = pressed,
+            Key::Start => self.start = pressed,
+            Key::Select => self.select = pressed,
+            Key::Left => self.left = pressed,
+            Key::Right => self.right = pressed,
+            Key::Up => self.up = pressed,
+            Key::Down => self.down = pressed,
+        }
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        match key {
+            Key::A => self.a,
+            Key::B => self.b,
+            Key::Start => self.start,
+            Key::Select => self.select,
+            Key::Left => self.left,
+            Key::Right => self.right,
+            Key::Up => self.up,
+            Key::Down => self.down,
         }
     }
-    pub fn step(&mut self) {
-        self.event_pump.pump_events();
-        let state = sdl2::keyboard::KeyboardState::new(&self.event_pump);
-
-        self.left = state.is_scancode_pressed(Scancode::Left);
-        self.right = state.is_scancode_pressed(Scancode::Right);
-        self.up = state.is_scancode_pressed(Scancode::Up);
-        self.down = state.is_scancode_pressed(Scancode::Down);
-        self.a = state.is_scancode_pressed(Scancode::A);
-        self.b = state.is_scancode_pressed(Scancode::B);
-        self.start = state.is_scancode_pressed(Scancode::Space);
-        self.select = state.is_scancode_pressed(Scancode::Backslash);
+
+    pub fn any_pressed(&self) -> bool {
+        *self != JoypadState::default()
     }
 
+    /// Lower nibble of P1 when the button keys are selected (active low).
     pub fn get_keys1(&self) -> u8 {
         let mut keys1 = 0u8;
 
@@ -50,8 +67,10 @@ impl Input {
         keys1 |= if self.b { 0 } else { 1 << 1 };
         keys1 |= if self.a { 0 } else { 1 << 0 };
 
-        return keys1;
+        keys1
     }
+
+    /// Lower nibble of P1 when the direction keys are selected (active low).
     pub fn get_keys2(&self) -> u8 {
         let mut keys1 = 0u8;
 
@@ -60,6 +79,44 @@ impl Input {
         keys1 |= if self.left { 0 } else { 1 << 1 };
         keys1 |= if self.right { 0 } else { 1 << 0 };
 
-        return keys1;
+        keys1
+    }
+}
+
+/// Anything that can feed button state into the emulator: keyboard polling,
+/// replay files, scripted bots or network peers.
+/// `System` polls its source once at the start of every frame.
+pub trait JoypadSource {
+    fn poll(&mut self) -> JoypadState;
+}
+
+/// Called with `true` when the rumble motor of a cartridge starts and `false` when it
+/// stops, frontends can use it to vibrate a gamepad.
+pub type RumbleCallback = Box<dyn FnMut(bool) + Send>;
+
+/// Plays back a recorded list of per-frame states and releases all buttons once it runs out.
+pub struct Replay {
+    frames: Vec<JoypadState>,
+    position: usize,
+}
+
+impl Replay {
+    pub fn new(frames: Vec<JoypadState>) -> Replay {
+        Replay {
+            frames,
+            position: 0,
+        }
     }
-}
\ No newline at end of file
+
+    pub fn finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+}
+
+impl JoypadSource for Replay {
+    fn poll(&mut self) -> JoypadState {
+        let state = self.frames.get(self.position).cloned().unwrap_or_default();
+        self.position += 1;
+        state
+    }
+}
@@ -1,150 +1,104 @@
-use gb::mmu::*;
-use gb::registers::*;
-use gb::gpu::Gpu;
-use gb::display::*;
-
-use std::cell::RefCell;
-use std::rc::Rc;
+use gb::bus::Bus;
+use gb::registers::Registers;
 
+/// The interrupt master enable flag (IME). IE and IF themselves live on the bus.
+#[derive(Debug)]
 pub struct Interrupts {
     pub master: bool,
-    mmu: Rc<RefCell<Mmu>>,
-    regs: Rc<RefCell<Registers>>,
-    gpu: Rc<RefCell<Gpu>>,
+    /// Set by EI, IME only takes effect after the following instruction.
+    enable_pending: bool,
 }
 
 pub const INTERRUPT_ENABLE: u16 = 0xFFFF;
 pub const INTERRUPT_FLAG: u16 = 0xFF0F;
 
-// const NONE: u8 = 0;
-pub const VBLANK: u8 = (1 << 0);
-const LCDSTAT: u8 = (1 << 1);
-const TIMER: u8 = (1 << 2);
-const SERIAL: u8 = (1 << 3);
-const JOYPAD: u8 = (1 << 4);
+pub const VBLANK: u8 = 1 << 0;
+pub const LCDSTAT: u8 = 1 << 1;
+pub const TIMER: u8 = 1 << 2;
+pub const SERIAL: u8 = 1 << 3;
+pub const JOYPAD: u8 = 1 << 4;
+
+impl Default for Interrupts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Interrupts {
-    pub fn new(mmu: Rc<RefCell<Mmu>>,
-               regs: Rc<RefCell<Registers>>,
-               gpu: Rc<RefCell<Gpu>>)
-               -> Interrupts {
+    pub fn new() -> Interrupts {
         Interrupts {
             master: true,
-            mmu: mmu,
-            regs: regs,
-            gpu: gpu,
+            enable_pending: false,
         }
     }
 
     pub fn disable_interrupts(&mut self) {
         self.master = false;
+        self.enable_pending = false;
     }
 
+    /// EI, interrupts are enabled after the next instruction.
     pub fn enable_interrupts(&mut self) {
-        self.master = true;
-    }
-
-
-    pub fn step(&mut self, mut display: &mut SdlDisplay) -> i32 {
-        let mut flags = self.mmu.borrow_mut().read_u8(INTERRUPT_FLAG);
-        let enable = self.mmu.borrow_mut().read_u8(INTERRUPT_ENABLE);
-        let mut ticks = 0;
-        let master = self.master;
-
-        if master && enable != 0 && flags != 0 {
-            let fire = enable & flags;
-            if fire & VBLANK != 0 {
-                self.handle_vblank(&mut display);
-                flags &= !VBLANK;
-                self.mmu.borrow_mut().write_u8(INTERRUPT_FLAG, flags);
-                ticks = 12;
-            }
-            if fire & LCDSTAT != 0 {
-                self.handle_lcdstat();
-                flags &= !LCDSTAT;
-                self.mmu.borrow_mut().write_u8(INTERRUPT_FLAG, flags);
-                ticks = 12;
-            }
-            if fire & TIMER != 0 {
-                self.handle_timer();
-                flags &= !TIMER;
-                self.mmu.borrow_mut().write_u8(INTERRUPT_FLAG, flags);
-                ticks = 12;
-            }
-            if fire & SERIAL != 0 {
-                self.handle_serial();
-                flags &= !SERIAL;
-                self.mmu.borrow_mut().write_u8(INTERRUPT_FLAG, flags);
-                ticks = 12;
-            }
-            if fire & JOYPAD != 0 {
-                self.handle_joypad();
-                flags &= !JOYPAD;
-                self.mmu.borrow_mut().write_u8(INTERRUPT_FLAG, flags);
-                ticks = 12;
-            }
-        }
-
-        ticks
+        self.enable_pending = true;
     }
 
-
-    fn push_pc(&self) {
-        let pc = self.regs.borrow_mut().pc;
-        let mut sp = self.regs.borrow_mut().sp;
-        sp -= 2;
-        self.regs.borrow_mut().sp = sp;
-        self.mmu.borrow_mut().write_u16(sp, pc);
+    /// Interrupts that are both requested and enabled, regardless of the master flag.
+    pub fn pending(&self, bus: &Bus) -> u8 {
+        bus.read_u8(INTERRUPT_FLAG) & bus.read_u8(INTERRUPT_ENABLE) & 0x1F
     }
 
-    pub fn handle_vblank(&mut self, mut display: &mut SdlDisplay) {
-        self.push_pc();
-        self.master = false;
-        self.regs.borrow_mut().pc = 0x40;
-
-        // TODO cpu ticks += 12
-        display.draw(self.gpu.borrow().framebuffer);
-        self.gpu.borrow_mut().clear_framebuffer();
-    }
-    pub fn handle_lcdstat(&mut self) {
-        println!("lcdstat");
-        self.push_pc();
-        self.master = false;
-        self.regs.borrow_mut().pc = 0x48;
-
+    /// Runs between two instructions. Dispatches the highest priority pending interrupt
+    /// if IME is set, which takes 5 m-cycles.
+    pub fn step(&mut self, regs: &mut Registers, bus: &mut Bus) {
+        if self.master && self.pending(bus) != 0 {
+            self.dispatch(regs, bus);
+        }
 
+        if self.enable_pending {
+            self.enable_pending = false;
+            self.master = true;
+        }
     }
-    pub fn handle_timer(&mut self) {
-        println!("timer");
-        self.push_pc();
-        self.master = false;
-        self.regs.borrow_mut().pc = 0x50;
 
-    }
-    pub fn handle_serial(&mut self) {
-        print!("serial");
-        self.push_pc();
+    fn dispatch(&mut self, regs: &mut Registers, bus: &mut Bus) {
         self.master = false;
-        self.regs.borrow_mut().pc = 0x58;
+        bus.tick();
+        bus.tick();
+
+        let pc = regs.pc;
+        let sp = regs.sp.wrapping_sub(1);
+        bus.tick();
+        bus.write_u8(sp, (pc >> 8) as u8);
+
+        // the interrupt is only chosen after the upper byte of pc was pushed. If that
+        // push overwrote IE and nothing is pending anymore the dispatch is cancelled
+        // and the cpu jumps to 0x0000 instead.
+        let pending = self.pending(bus);
+
+        let sp = sp.wrapping_sub(1);
+        bus.tick();
+        bus.write_u8(sp, pc as u8);
+        regs.sp = sp;
+        bus.tick();
+
+        if pending == 0 {
+            regs.pc = 0x0000;
+            return;
+        }
 
-    }
-    pub fn handle_joypad(&mut self) {
-        print!("joypad");
-        self.push_pc();
-        self.master = false;
-        self.regs.borrow_mut().pc = 0x60;
+        let interrupt = pending & pending.wrapping_neg();
+        let flags = bus.read_u8(INTERRUPT_FLAG);
+        bus.write_u8(INTERRUPT_FLAG, flags & !interrupt);
+        regs.pc = vector(interrupt);
     }
 }
 
-use std::fmt;
-impl fmt::Debug for Interrupts {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let flag = self.mmu.borrow_mut().read_u8(INTERRUPT_FLAG);
-        let enable = self.mmu.borrow_mut().read_u8(INTERRUPT_ENABLE);
-        write!(f,
-               "MASTER: {} ENABLE: {:02X} FLAG: {:02X}",
-               if self.master { 1 } else { 0 },
-               enable,
-               flag)
+fn vector(interrupt: u8) -> u16 {
+    match interrupt {
+        VBLANK => 0x40,
+        LCDSTAT => 0x48,
+        TIMER => 0x50,
+        SERIAL => 0x58,
+        _ => 0x60,
     }
-}
\ No newline at end of file
+}
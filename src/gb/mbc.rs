@@ -0,0 +1,1001 @@
+use gb::catridge::*;
+use gb::error::EmuError;
+use gb::input::RumbleCallback;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Creates the memory bank controller the cartridge header asks for.
+pub fn new(cart: Cartrige) -> Result<Box<dyn Mbc>, EmuError> {
+    let ram_size = cart.header.ram_size.unwrap_or(0);
+    let cartridge_type = cart.cartridge_type();
+    let mbc: Box<dyn Mbc> = match cartridge_type {
+        CartridgeType::Mbc1 |
+        CartridgeType::Mbc1Ram |
+        CartridgeType::Mbc1RamBattery => Box::new(Mbc1::new(cart.rom, ram_size)),
+        CartridgeType::Mbc2 | CartridgeType::Mbc2Battery => Box::new(Mbc2::new(cart.rom)),
+        CartridgeType::Mbc3TimerBattery |
+        CartridgeType::Mbc3TimerRamBattery |
+        CartridgeType::Mbc3 |
+        CartridgeType::Mbc3Ram |
+        CartridgeType::Mbc3RamBattery => {
+            Box::new(Mbc3::new(cart.rom, ram_size, cartridge_type.has_timer()))
+        }
+        CartridgeType::Mbc5 |
+        CartridgeType::Mbc5Ram |
+        CartridgeType::Mbc5RamBattery |
+        CartridgeType::Mbc5Rumble |
+        CartridgeType::Mbc5RumbleRam |
+        CartridgeType::Mbc5RumbleRamBattery => {
+            Box::new(Mbc5::new(cart.rom, ram_size, cartridge_type.has_rumble()))
+        }
+        CartridgeType::Plain |
+        CartridgeType::PlainRam |
+        CartridgeType::PlainRamBattery => Box::new(NoMbc::new(cart.rom)),
+        other => return Err(EmuError::UnsupportedMapper(other)),
+    };
+    Ok(mbc)
+}
+
+/// Memory bank controller of a cartridge, it sees every access to 0x0000-0x7FFF and
+/// 0xA000-0xBFFF.
+pub trait Mbc: Send {
+    fn read_u8(&self, addr: u16) -> u8;
+    fn write_u8(&mut self, addr: u16, value: u8);
+
+    /// Advances hardware on the cartridge by `ticks` clock ticks of real time, which
+    /// doesn't speed up in the CGB's double speed mode.
+    fn step(&mut self, _ticks: i32) {}
+
+    /// Lets a real-time clock follow the host's clock instead of the emulated cycles.
+    fn use_host_clock(&mut self, _enabled: bool) {}
+
+    /// Sets the callback that is told when a rumble motor starts or stops.
+    fn set_rumble_callback(&mut self, _callback: RumbleCallback) {}
+
+    /// True if accesses to 0xA000-0xBFFF reach the cartridge ram or clock.
+    fn ram_accessible(&self) -> bool {
+        false
+    }
+
+    /// The cartridge ram in the raw format of `.sav` files, followed by the clock state
+    /// if the cartridge has one.
+    fn save_data(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores what `save_data` returned, shorter or longer data is accepted.
+    fn load_save_data(&mut self, _data: &[u8]) {}
+}
+
+/// Copies as much of `data` into `ram` as fits.
+fn load_ram(ram: &mut [u8], data: &[u8]) {
+    let len = ram.len().min(data.len());
+    ram[..len].copy_from_slice(&data[..len]);
+}
+
+/// The logo every cartridge header carries at 0x0104.
+const NINTENDO_LOGO: [u8; 48] = [0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00,
+                                 0x83, 0x00, 0x0C, 0x00, 0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89,
+                                 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99, 0xBB,
+                                 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F,
+                                 0xBB, 0xB9, 0x33, 0x3E];
+const LOGO_OFFSET: usize = 0x0104;
+
+/// MBC1: up to 128 rom banks and 4 ram banks, the two bank registers are combined
+/// depending on the banking mode.
+struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    /// Lower 5 bits of the rom bank, 0 selects 1.
+    bank1: u8,
+    /// 2 more bits for the rom bank or the ram bank.
+    bank2: u8,
+    /// In mode 1 `bank2` also switches 0x0000-0x3FFF and the ram bank.
+    mode: bool,
+    /// MBC1M multicarts don't connect the highest bit of `bank1`, so `bank2` selects one
+    /// of four 256 KB games.
+    multicart: bool,
+}
+
+impl Mbc1 {
+    fn new(rom: Vec<u8>, ram_size: usize) -> Mbc1 {
+        Mbc1 {
+            multicart: is_multicart(&rom),
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+        }
+    }
+
+    fn bank2_shift(&self) -> u32 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn rom_byte(&self, bank: usize, addr: u16) -> u8 {
+        // rom sizes are powers of 2, the unused upper bank bits aren't connected
+        let banks = (self.rom.len() / ROM_BANK_SIZE).max(1);
+        let offset = (bank % banks) * ROM_BANK_SIZE + (addr as usize % ROM_BANK_SIZE);
+        self.rom.get(offset).cloned().unwrap_or(0xFF)
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let bank = if self.mode { self.bank2 as usize } else { 0 };
+        let offset = bank * RAM_BANK_SIZE + (addr - 0xA000) as usize;
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        let high = (self.bank2 as usize) << self.bank2_shift();
+        match addr {
+            0x0000..=0x3FFF => self.rom_byte(if self.mode { high } else { 0 }, addr),
+            0x4000..=0x7FFF => {
+                let low = if self.multicart { self.bank1 & 0x0F } else { self.bank1 };
+                self.rom_byte(high | low as usize, addr)
+            }
+            0xA000..=0xBFFF => self.ram_offset(addr).map_or(0xFF, |offset| self.ram[offset]),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            // the check for 0 uses all 5 bits, even on multicarts
+            0x2000..=0x3FFF => self.bank1 = (value & 0x1F).max(1),
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            0x6000..=0x7FFF => self.mode = value & 0x01 != 0,
+            0xA000..=0xBFFF => {
+                if let Some(offset) = self.ram_offset(addr) {
+                    self.ram[offset] = value;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ram_accessible(&self) -> bool {
+        self.ram_offset(0xA000).is_some()
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+/// MBC1M multicarts are 1 MB and have a second header with the logo for the game that
+/// starts at bank 0x10.
+fn is_multicart(rom: &[u8]) -> bool {
+    let logo = 0x10 * ROM_BANK_SIZE + LOGO_OFFSET;
+    rom.len() == 64 * ROM_BANK_SIZE && rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
+struct NoMbc {
+    rom: Vec<u8>,
+    ram: [u8; 0x2000],
+}
+
+impl Mbc for NoMbc {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7fff => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0xa000..=0xbfff => self.ram[(addr - 0xa000) as usize],
+            _ => 0xFF,
+        }
+    }
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        // TODO: are writes outside of ram really save to ignore?
+        if let 0xA000..=0xBFFF = addr {
+            self.ram[(addr - 0xA000) as usize] = value;
+        }
+    }
+
+    fn ram_accessible(&self) -> bool {
+        true
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+impl NoMbc {
+    fn new(rom: Vec<u8>) -> NoMbc {
+        NoMbc {
+            ram: [0; 0x2000],
+            rom,
+        }
+    }
+}
+
+/// Size of the ram built into the MBC2, every byte holds 4 bits.
+const MBC2_RAM_SIZE: usize = 0x200;
+
+/// MBC2: up to 16 rom banks and 512 half-bytes of ram inside the controller.
+struct Mbc2 {
+    rom: Vec<u8>,
+    /// One byte per half-byte with the upper bits cleared, the same 512 byte layout
+    /// other emulators use for MBC2 saves.
+    ram: [u8; MBC2_RAM_SIZE],
+    rom_bank: usize,
+    ram_enabled: bool,
+}
+
+impl Mbc2 {
+    fn new(rom: Vec<u8>) -> Mbc2 {
+        Mbc2 {
+            rom,
+            ram: [0; MBC2_RAM_SIZE],
+            rom_bank: 1,
+            ram_enabled: false,
+        }
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0x4000..=0x7FFF => {
+                let banks = (self.rom.len() / ROM_BANK_SIZE).max(1);
+                let offset = (self.rom_bank % banks) * ROM_BANK_SIZE + (addr - 0x4000) as usize;
+                self.rom.get(offset).cloned().unwrap_or(0xFF)
+            }
+            // the ram repeats across the whole area and the upper 4 bits aren't connected
+            0xA000..=0xBFFF if self.ram_enabled => {
+                0xF0 | self.ram[addr as usize % MBC2_RAM_SIZE]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            // bit 8 of the address selects between the ram enable and the rom bank
+            0x0000..=0x3FFF if addr & 0x100 == 0 => self.ram_enabled = value & 0x0F == 0x0A,
+            0x0000..=0x3FFF => self.rom_bank = ((value & 0x0F) as usize).max(1),
+            0xA000..=0xBFFF if self.ram_enabled => {
+                self.ram[addr as usize % MBC2_RAM_SIZE] = value & 0x0F
+            }
+            _ => {}
+        }
+    }
+
+    fn ram_accessible(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+/// MBC3: up to 128 rom banks, 4 ram banks and an optional real-time clock.
+struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: usize,
+    /// 0x00-0x03 selects a ram bank, 0x08-0x0C one of the clock registers.
+    ram_select: u8,
+    ram_enabled: bool,
+    rtc: Option<Rtc>,
+    /// The clock is latched by writing 0x00 and then 0x01 to 0x6000-0x7FFF.
+    latch_armed: bool,
+}
+
+impl Mbc3 {
+    fn new(rom: Vec<u8>, ram_size: usize, timer: bool) -> Mbc3 {
+        Mbc3 {
+            rom,
+            ram: vec![0; ram_size],
+            rom_bank: 1,
+            ram_select: 0,
+            ram_enabled: false,
+            rtc: if timer { Some(Rtc::new()) } else { None },
+            latch_armed: false,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let offset = self.ram_select as usize * RAM_BANK_SIZE + (addr - 0xA000) as usize;
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0x4000..=0x7FFF => {
+                let banks = (self.rom.len() / ROM_BANK_SIZE).max(1);
+                let offset = (self.rom_bank % banks) * ROM_BANK_SIZE + (addr - 0x4000) as usize;
+                self.rom.get(offset).cloned().unwrap_or(0xFF)
+            }
+            0xA000..=0xBFFF if self.ram_enabled => {
+                match (self.ram_select, &self.rtc) {
+                    (0x00..=0x03, _) => self.ram_offset(addr).map_or(0xFF, |offset| self.ram[offset]),
+                    (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_select),
+                    _ => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = ((value & 0x7F) as usize).max(1),
+            0x4000..=0x5FFF => self.ram_select = value,
+            0x6000..=0x7FFF => {
+                if self.latch_armed && value == 0x01 {
+                    if let Some(ref mut rtc) = self.rtc {
+                        rtc.latch();
+                    }
+                }
+                self.latch_armed = value == 0x00;
+            }
+            0xA000..=0xBFFF if self.ram_enabled => {
+                match self.ram_select {
+                    0x00..=0x03 => {
+                        if let Some(offset) = self.ram_offset(addr) {
+                            self.ram[offset] = value;
+                        }
+                    }
+                    0x08..=0x0C => {
+                        if let Some(ref mut rtc) = self.rtc {
+                            rtc.write(self.ram_select, value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn step(&mut self, ticks: i32) {
+        if let Some(ref mut rtc) = self.rtc {
+            rtc.step(ticks);
+        }
+    }
+
+    fn use_host_clock(&mut self, enabled: bool) {
+        if let Some(ref mut rtc) = self.rtc {
+            rtc.use_host_clock(enabled);
+        }
+    }
+
+    fn ram_accessible(&self) -> bool {
+        self.ram_enabled &&
+        match self.ram_select {
+            0x00..=0x03 => !self.ram.is_empty(),
+            0x08..=0x0C => self.rtc.is_some(),
+            _ => false,
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(ref rtc) = self.rtc {
+            data.extend_from_slice(&rtc.footer());
+        }
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+        if let (Some(ref mut rtc), Some(footer)) = (&mut self.rtc, data.get(self.ram.len()..)) {
+            rtc.load_footer(footer);
+        }
+    }
+}
+
+/// MBC5: up to 512 rom banks, 16 ram banks and a rumble motor on some cartridges.
+struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// 9-bit bank number, unlike the other mappers bank 0 can be selected.
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_enabled: bool,
+    /// Rumble cartridges use bit 3 of the ram bank number for the motor.
+    rumble: bool,
+    motor: bool,
+    rumble_callback: Option<RumbleCallback>,
+}
+
+impl Mbc5 {
+    fn new(rom: Vec<u8>, ram_size: usize, rumble: bool) -> Mbc5 {
+        Mbc5 {
+            rom,
+            ram: vec![0; ram_size],
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            rumble,
+            motor: false,
+            rumble_callback: None,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let offset = self.ram_bank * RAM_BANK_SIZE + (addr - 0xA000) as usize;
+        Some(offset % self.ram.len())
+    }
+
+    fn set_motor(&mut self, on: bool) {
+        if on == self.motor {
+            return;
+        }
+        self.motor = on;
+        if let Some(ref mut callback) = self.rumble_callback {
+            callback(on);
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0x4000..=0x7FFF => {
+                let banks = (self.rom.len() / ROM_BANK_SIZE).max(1);
+                let offset = (self.rom_bank % banks) * ROM_BANK_SIZE + (addr - 0x4000) as usize;
+                self.rom.get(offset).cloned().unwrap_or(0xFF)
+            }
+            0xA000..=0xBFFF => self.ram_offset(addr).map_or(0xFF, |offset| self.ram[offset]),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as usize,
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | ((value as usize & 0x01) << 8)
+            }
+            0x4000..=0x5FFF => {
+                if self.rumble {
+                    self.ram_bank = (value & 0x07) as usize;
+                    self.set_motor(value & 0x08 != 0);
+                } else {
+                    self.ram_bank = (value & 0x0F) as usize;
+                }
+            }
+            0xA000..=0xBFFF => {
+                if let Some(offset) = self.ram_offset(addr) {
+                    self.ram[offset] = value;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+        self.rumble_callback = Some(callback);
+    }
+
+    fn ram_accessible(&self) -> bool {
+        self.ram_offset(0xA000).is_some()
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+/// Clock ticks in one second of real time.
+const TICKS_PER_SECOND: i64 = 4_194_304;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// The clock state other emulators append to MBC3 saves: the live and the latched
+/// registers as 32-bit values and a 64-bit unix timestamp, all little endian. Older
+/// saves have a 32-bit timestamp.
+const RTC_FOOTER_SIZE: usize = 48;
+const RTC_FOOTER_SIZE_OLD: usize = 44;
+
+/// The MBC3 real-time clock. Reads see the copy that was made at the last latch.
+struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// 9-bit day counter.
+    days: u16,
+    halted: bool,
+    /// Set when the day counter overflows, stays set until it's cleared by a write.
+    carry: bool,
+    latched: [u8; 5],
+    /// Clock ticks since the last full second.
+    ticks: i64,
+    /// Time up to which the host clock was counted, `None` if the emulated cycles drive
+    /// the clock.
+    host_time: Option<SystemTime>,
+}
+
+impl Rtc {
+    fn new() -> Rtc {
+        Rtc {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halted: false,
+            carry: false,
+            latched: [0; 5],
+            ticks: 0,
+            host_time: None,
+        }
+    }
+
+    fn use_host_clock(&mut self, enabled: bool) {
+        self.host_time = if enabled { Some(SystemTime::now()) } else { None };
+    }
+
+    fn step(&mut self, ticks: i32) {
+        if self.host_time.is_some() {
+            // checking the host clock on every m-cycle would be wasteful, once per
+            // emulated second is plenty
+            self.ticks += ticks as i64;
+            if self.ticks >= TICKS_PER_SECOND {
+                self.ticks = 0;
+                self.sync_host_clock();
+            }
+            return;
+        }
+        if self.halted {
+            return;
+        }
+        self.ticks += ticks as i64;
+        while self.ticks >= TICKS_PER_SECOND {
+            self.ticks -= TICKS_PER_SECOND;
+            self.tick_second();
+        }
+    }
+
+    /// Counts the whole seconds that passed on the host since the last sync.
+    fn sync_host_clock(&mut self) {
+        let last = match self.host_time {
+            Some(last) => last,
+            None => return,
+        };
+        let now = SystemTime::now();
+        let elapsed = now.duration_since(last).map(|elapsed| elapsed.as_secs()).unwrap_or(0);
+        if self.halted {
+            self.host_time = Some(now);
+            return;
+        }
+        self.advance(elapsed);
+        self.host_time = Some(last + Duration::from_secs(elapsed));
+    }
+
+    /// The counters only carry when they reach exactly 60, 60 and 24. Out of range
+    /// values written by the game count up to the register's limit and wrap to 0.
+    fn tick_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days = (self.days + 1) & 0x1FF;
+        if self.days == 0 {
+            self.carry = true;
+        }
+    }
+
+    /// Lets `seconds` pass at once, as if `tick_second` was called that often.
+    fn advance(&mut self, mut seconds: u64) {
+        // out of range values have to count up and wrap on their own first
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick_second();
+            seconds -= 1;
+        }
+        let time = self.hours as u64 * 3600 + self.minutes as u64 * 60 + self.seconds as u64 +
+                   seconds;
+        self.seconds = (time % 60) as u8;
+        self.minutes = (time / 60 % 60) as u8;
+        self.hours = (time / 3600 % 24) as u8;
+        let days = self.days as u64 + time / SECONDS_PER_DAY;
+        if days > 0x1FF {
+            self.carry = true;
+        }
+        self.days = (days & 0x1FF) as u16;
+    }
+
+    fn registers(&self) -> [u8; 5] {
+        let halt = if self.halted { 0x40 } else { 0 };
+        let carry = if self.carry { 0x80 } else { 0 };
+        [self.seconds,
+         self.minutes,
+         self.hours,
+         self.days as u8,
+         carry | halt | (self.days >> 8) as u8]
+    }
+
+    fn set_registers(&mut self, registers: [u8; 5]) {
+        self.seconds = registers[0] & 0x3F;
+        self.minutes = registers[1] & 0x3F;
+        self.hours = registers[2] & 0x1F;
+        self.days = registers[3] as u16 | ((registers[4] as u16 & 0x01) << 8);
+        self.halted = registers[4] & 0x40 != 0;
+        self.carry = registers[4] & 0x80 != 0;
+    }
+
+    fn footer(&self) -> [u8; RTC_FOOTER_SIZE] {
+        let mut footer = [0; RTC_FOOTER_SIZE];
+        let live = self.registers();
+        for (index, &value) in live.iter().chain(self.latched.iter()).enumerate() {
+            footer[index * 4] = value;
+        }
+        footer[40..].copy_from_slice(&unix_time().to_le_bytes());
+        footer
+    }
+
+    /// Restores the registers from a save and lets the time pass that went by since it
+    /// was written, the clock on the cartridge keeps running while it's unplugged.
+    fn load_footer(&mut self, footer: &[u8]) {
+        if footer.len() != RTC_FOOTER_SIZE && footer.len() != RTC_FOOTER_SIZE_OLD {
+            return;
+        }
+        let mut registers = [0; 10];
+        for (index, register) in registers.iter_mut().enumerate() {
+            *register = footer[index * 4];
+        }
+        self.set_registers([registers[0], registers[1], registers[2], registers[3], registers[4]]);
+        self.latched.copy_from_slice(&registers[5..]);
+
+        let mut timestamp = [0; 8];
+        timestamp[..footer.len() - 40].copy_from_slice(&footer[40..]);
+        let saved = u64::from_le_bytes(timestamp);
+        if !self.halted {
+            self.advance(unix_time().saturating_sub(saved));
+        }
+        if self.host_time.is_some() {
+            self.host_time = Some(SystemTime::now());
+        }
+    }
+
+    fn latch(&mut self) {
+        self.sync_host_clock();
+        self.latched = self.registers();
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        self.latched[(register - 0x08) as usize]
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        self.sync_host_clock();
+        match register {
+            0x08 => {
+                self.seconds = value & 0x3F;
+                // writing the seconds resets the divider that counts them
+                self.ticks = 0;
+            }
+            0x09 => self.minutes = value & 0x3F,
+            0x0A => self.hours = value & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | value as u16,
+            _ => {
+                self.days = (self.days & 0xFF) | ((value as u16 & 0x01) << 8);
+                self.halted = value & 0x40 != 0;
+                self.carry = value & 0x80 != 0;
+            }
+        }
+        self.latched[(register - 0x08) as usize] = self.registers()[(register - 0x08) as usize];
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mbc3(banks: usize) -> Mbc3 {
+        let mut mbc = Mbc3::new(numbered_rom(banks), 0x8000, true);
+        mbc.write_u8(0x0000, 0x0A);
+        mbc
+    }
+
+    fn select_rtc(mbc: &mut Mbc3, register: u8) -> u8 {
+        mbc.write_u8(0x4000, register);
+        mbc.read_u8(0xA000)
+    }
+
+    fn latch(mbc: &mut Mbc3) {
+        mbc.write_u8(0x6000, 0x00);
+        mbc.write_u8(0x6000, 0x01);
+    }
+
+    fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom
+    }
+
+    #[test]
+    fn mbc1_masks_banks_to_the_rom_size() {
+        let mut mbc = Mbc1::new(numbered_rom(8), 0);
+        mbc.write_u8(0x2000, 0x00);
+        assert_eq!(mbc.read_u8(0x4000), 1);
+        mbc.write_u8(0x2000, 0x1D);
+        assert_eq!(mbc.read_u8(0x4000), 5);
+        // only the lower 5 bits are written, so 0x20 selects bank 1
+        mbc.write_u8(0x2000, 0x20);
+        assert_eq!(mbc.read_u8(0x4000), 1);
+        mbc.write_u8(0x4000, 0x03);
+        assert_eq!(mbc.read_u8(0x4000), 1);
+    }
+
+    #[test]
+    fn mbc1_mode_1_switches_bank_0_and_ram() {
+        let mut mbc = Mbc1::new(numbered_rom(128), 4 * RAM_BANK_SIZE);
+        mbc.write_u8(0x4000, 0x02);
+        mbc.write_u8(0x2000, 0x03);
+        assert_eq!(mbc.read_u8(0x0000), 0x00);
+        assert_eq!(mbc.read_u8(0x4000), 0x43);
+        mbc.write_u8(0x6000, 0x01);
+        assert_eq!(mbc.read_u8(0x0000), 0x40);
+
+        assert_eq!(mbc.read_u8(0xA000), 0xFF);
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0xA000, 0x12);
+        mbc.write_u8(0x6000, 0x00);
+        assert_eq!(mbc.read_u8(0xA000), 0x00);
+        mbc.write_u8(0x6000, 0x01);
+        assert_eq!(mbc.read_u8(0xA000), 0x12);
+        mbc.write_u8(0x0000, 0x00);
+        assert_eq!(mbc.read_u8(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc1_multicarts_are_detected_from_the_second_logo() {
+        let mut rom = numbered_rom(64);
+        assert!(!Mbc1::new(rom.clone(), 0).multicart);
+        let logo = 0x10 * ROM_BANK_SIZE + LOGO_OFFSET;
+        rom[logo..logo + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        let mut mbc = Mbc1::new(rom, 0);
+        assert!(mbc.multicart);
+
+        mbc.write_u8(0x4000, 0x01);
+        mbc.write_u8(0x2000, 0x12);
+        assert_eq!(mbc.read_u8(0x4000), 0x12);
+        mbc.write_u8(0x6000, 0x01);
+        assert_eq!(mbc.read_u8(0x0000), 0x10);
+    }
+
+    #[test]
+    fn mbc2_selects_registers_with_address_bit_8() {
+        let mut mbc = Mbc2::new(numbered_rom(16));
+
+        mbc.write_u8(0x2000, 0x05);
+        assert_eq!(mbc.read_u8(0x4000), 1);
+        mbc.write_u8(0x2100, 0x05);
+        assert_eq!(mbc.read_u8(0x4000), 5);
+        mbc.write_u8(0x0100, 0x00);
+        assert_eq!(mbc.read_u8(0x4000), 1);
+
+        mbc.write_u8(0xA000, 0x0C);
+        assert_eq!(mbc.read_u8(0xA000), 0xFF);
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0xA000, 0x3C);
+        assert_eq!(mbc.read_u8(0xA000), 0xFC);
+        assert_eq!(mbc.read_u8(0xA200), 0xFC);
+        assert_eq!(mbc.read_u8(0xBE00), 0xFC);
+        assert_eq!(mbc.ram[0], 0x0C);
+    }
+
+    #[test]
+    fn mbc3_ram_is_accessible_only_if_it_exists() {
+        let mut mbc = Mbc3::new(numbered_rom(2), 0, false);
+        mbc.write_u8(0x0000, 0x0A);
+        assert!(!mbc.ram_accessible());
+        mbc.write_u8(0x4000, 0x08);
+        assert!(!mbc.ram_accessible());
+
+        let mut mbc = mbc3(2);
+        assert!(mbc.ram_accessible());
+        mbc.write_u8(0x0000, 0x00);
+        assert!(!mbc.ram_accessible());
+    }
+
+    #[test]
+    fn mbc3_clock_registers_are_accessible_without_ram() {
+        let mut mbc = Mbc3::new(numbered_rom(2), 0, true);
+        mbc.write_u8(0x0000, 0x0A);
+        assert!(!mbc.ram_accessible());
+        mbc.write_u8(0x4000, 0x08);
+        assert!(mbc.ram_accessible());
+        mbc.write_u8(0x4000, 0x0D);
+        assert!(!mbc.ram_accessible());
+    }
+
+    #[test]
+    fn mbc3_switches_rom_and_ram_banks() {
+        let mut mbc = mbc3(128);
+        mbc.write_u8(0x2000, 0x00);
+        assert_eq!(mbc.read_u8(0x4000), 1);
+        mbc.write_u8(0x2000, 0x7F);
+        assert_eq!(mbc.read_u8(0x4000), 0x7F);
+
+        for bank in 0..4 {
+            mbc.write_u8(0x4000, bank);
+            mbc.write_u8(0xA000, 0x10 + bank);
+        }
+        mbc.write_u8(0x4000, 2);
+        assert_eq!(mbc.read_u8(0xA000), 0x12);
+        mbc.write_u8(0x0000, 0x00);
+        assert_eq!(mbc.read_u8(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc5_uses_9_bit_rom_banks_and_16_ram_banks() {
+        let mut rom = vec![0; 512 * ROM_BANK_SIZE];
+        for bank in 0..512 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        let mut mbc = Mbc5::new(rom, 16 * RAM_BANK_SIZE, false);
+
+        mbc.write_u8(0x2000, 0x00);
+        assert_eq!(mbc.read_u8(0x4000), 0);
+        mbc.write_u8(0x2000, 0x23);
+        mbc.write_u8(0x3000, 0x01);
+        assert_eq!((mbc.read_u8(0x4000), mbc.read_u8(0x4001)), (0x23, 0x01));
+
+        mbc.write_u8(0x0000, 0x0A);
+        for bank in 0..16 {
+            mbc.write_u8(0x4000, bank);
+            mbc.write_u8(0xA000, bank);
+        }
+        mbc.write_u8(0x4000, 0x0F);
+        assert_eq!(mbc.read_u8(0xA000), 0x0F);
+        mbc.write_u8(0x0000, 0x00);
+        assert_eq!(mbc.read_u8(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc5_rumble_reports_motor_changes() {
+        use std::sync::{Arc, Mutex};
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = changes.clone();
+        let mut mbc = Mbc5::new(vec![0; 4 * ROM_BANK_SIZE], 4 * RAM_BANK_SIZE, true);
+        mbc.set_rumble_callback(Box::new(move |on| recorded.lock().unwrap().push(on)));
+
+        mbc.write_u8(0x4000, 0x09);
+        mbc.write_u8(0x4000, 0x0B);
+        mbc.write_u8(0x4000, 0x01);
+        assert_eq!(*changes.lock().unwrap(), vec![true, false]);
+        assert_eq!(mbc.ram_bank, 1);
+    }
+
+    #[test]
+    fn rtc_reads_the_latched_time() {
+        let mut mbc = mbc3(2);
+        mbc.step((TICKS_PER_SECOND * 61) as i32);
+        assert_eq!(select_rtc(&mut mbc, 0x08), 0);
+
+        latch(&mut mbc);
+        mbc.step(TICKS_PER_SECOND as i32);
+        assert_eq!(select_rtc(&mut mbc, 0x08), 1);
+        assert_eq!(select_rtc(&mut mbc, 0x09), 1);
+
+        // writing 0x01 again without 0x00 first doesn't latch
+        mbc.write_u8(0x6000, 0x01);
+        assert_eq!(select_rtc(&mut mbc, 0x08), 1);
+        latch(&mut mbc);
+        assert_eq!(select_rtc(&mut mbc, 0x08), 2);
+    }
+
+    #[test]
+    fn rtc_state_is_saved_in_the_footer() {
+        let mut mbc = mbc3(2);
+        mbc.write_u8(0xA000, 0x42);
+        for &(register, value) in &[(0x08, 12), (0x09, 34), (0x0A, 5), (0x0B, 0x10), (0x0C, 0x41)] {
+            mbc.write_u8(0x4000, register);
+            mbc.write_u8(0xA000, value);
+        }
+
+        let mut data = mbc.save_data();
+        assert_eq!(data.len(), 0x8000 + RTC_FOOTER_SIZE);
+        assert_eq!(&data[0x8000..0x8000 + 8], &[12, 0, 0, 0, 34, 0, 0, 0]);
+
+        // a day ago, the clock is halted so it doesn't catch up
+        let timestamp = unix_time() - SECONDS_PER_DAY;
+        data[0x8000 + 40..].copy_from_slice(&timestamp.to_le_bytes());
+        let mut loaded = mbc3(2);
+        loaded.load_save_data(&data);
+        latch(&mut loaded);
+        assert_eq!(select_rtc(&mut loaded, 0x09), 34);
+        assert_eq!(select_rtc(&mut loaded, 0x0C), 0x41);
+        assert_eq!(select_rtc(&mut loaded, 0x00), 0x42);
+
+        data[0x8000 + 16] = 0x01;
+        loaded.load_save_data(&data);
+        latch(&mut loaded);
+        assert_eq!(select_rtc(&mut loaded, 0x0B), 0x11);
+    }
+
+    #[test]
+    fn rtc_halt_stops_the_clock() {
+        let mut mbc = mbc3(2);
+        mbc.write_u8(0x4000, 0x0C);
+        mbc.write_u8(0xA000, 0x40);
+        mbc.step((TICKS_PER_SECOND * 5) as i32);
+        latch(&mut mbc);
+
+        assert_eq!(select_rtc(&mut mbc, 0x08), 0);
+        assert_eq!(select_rtc(&mut mbc, 0x0C), 0x40);
+    }
+
+    #[test]
+    fn rtc_day_counter_overflow_sets_the_carry() {
+        let mut mbc = mbc3(2);
+        for &(register, value) in &[(0x08, 59), (0x09, 59), (0x0A, 23), (0x0B, 0xFF), (0x0C, 0x01)] {
+            mbc.write_u8(0x4000, register);
+            mbc.write_u8(0xA000, value);
+        }
+        mbc.step(TICKS_PER_SECOND as i32);
+        latch(&mut mbc);
+
+        assert_eq!(select_rtc(&mut mbc, 0x0A), 0);
+        assert_eq!(select_rtc(&mut mbc, 0x0B), 0);
+        assert_eq!(select_rtc(&mut mbc, 0x0C), 0x80);
+
+        // the carry stays set until it's written
+        for _ in 0..24 * 60 {
+            mbc.step((TICKS_PER_SECOND * 60) as i32);
+        }
+        latch(&mut mbc);
+        assert_eq!(select_rtc(&mut mbc, 0x0B), 1);
+        assert_eq!(select_rtc(&mut mbc, 0x0C), 0x80);
+    }
+}
@@ -1,10 +1,16 @@
 pub mod catridge;
 pub mod system;
 pub mod cpu;
-pub mod mmu;
+pub mod bus;
 pub mod registers;
 pub mod gpu;
+pub mod palette;
+pub mod png;
 pub mod interrupts;
 pub mod component;
 pub mod display;
-pub mod input;
\ No newline at end of file
+pub mod input;
+pub mod error;
+pub mod timer;
+pub mod dma;
+mod mbc;
@@ -0,0 +1,169 @@
+use gb::error::EmuError;
+use gb::gpu::Color;
+
+use std::fs;
+use std::path::Path;
+
+/// The four colors the DMG shades map to, from the lightest to the darkest.
+pub type Shades = [Color; 4];
+
+const fn rgb(hex: u32) -> Color {
+    Color {
+        r: (hex >> 16) as u8,
+        g: (hex >> 8) as u8,
+        b: hex as u8,
+    }
+}
+
+pub const GREY: Shades = [rgb(0xFFFFFF), rgb(0xC0C0C0), rgb(0x606060), rgb(0x000000)];
+/// The yellowish green of the original DMG screen.
+pub const GREEN: Shades = [rgb(0x9BBC0F), rgb(0x8BAC0F), rgb(0x306230), rgb(0x0F380F)];
+pub const POCKET: Shades = [rgb(0xC4CFA1), rgb(0x8B956D), rgb(0x4D533C), rgb(0x1F1F1F)];
+/// The blue-green backlight of the Game Boy Light.
+pub const LIGHT: Shades = [rgb(0x00B581), rgb(0x009A71), rgb(0x00694A), rgb(0x004F3B)];
+
+/// Colors used for the background and the two sprite palettes. They can differ like
+/// with the CGB's colorization of DMG games.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DmgPalette {
+    pub bg: Shades,
+    pub obj0: Shades,
+    pub obj1: Shades,
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        DmgPalette::uniform(GREY)
+    }
+}
+
+impl DmgPalette {
+    /// The same colors for the background and both sprite palettes.
+    pub fn uniform(shades: Shades) -> DmgPalette {
+        DmgPalette {
+            bg: shades,
+            obj0: shades,
+            obj1: shades,
+        }
+    }
+
+    /// Looks up one of the built-in presets: grey, green, pocket or light.
+    pub fn preset(name: &str) -> Option<DmgPalette> {
+        let shades = match name.to_lowercase().as_str() {
+            "grey" | "gray" => GREY,
+            "green" | "dmg" => GREEN,
+            "pocket" => POCKET,
+            "light" => LIGHT,
+            _ => return None,
+        };
+        Some(DmgPalette::uniform(shades))
+    }
+
+    /// Parses a palette config. Every line is either a comment starting with `;` or a
+    /// `key = value` pair:
+    ///
+    /// ```text
+    /// ; start from a preset and give the sprites their own colors
+    /// preset = green
+    /// obj0 = #FFFFFF #FF8484 #943A3A #000000
+    /// obj1 = FFFFFF, 63A5FF, 0000FF, 000000
+    /// ```
+    ///
+    /// `colors` sets all three palettes at once, `bg`, `obj0` and `obj1` set one each.
+    pub fn parse(config: &str) -> Result<DmgPalette, EmuError> {
+        let mut palette = DmgPalette::default();
+        for (index, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let error = |reason: &str| {
+                EmuError::InvalidPalette {
+                    line: index + 1,
+                    reason: reason.to_string(),
+                }
+            };
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().ok_or_else(|| error("expected `key = value`"))?.trim();
+            if key == "preset" {
+                palette = DmgPalette::preset(value).ok_or_else(|| error("unknown preset"))?;
+                continue;
+            }
+
+            let shades = parse_shades(value).ok_or_else(|| error("expected 4 hex colors"))?;
+            match key {
+                "colors" => palette = DmgPalette::uniform(shades),
+                "bg" => palette.bg = shades,
+                "obj0" => palette.obj0 = shades,
+                "obj1" => palette.obj1 = shades,
+                _ => return Err(error("unknown key")),
+            }
+        }
+        Ok(palette)
+    }
+
+    pub fn from_path(path: &Path) -> Result<DmgPalette, EmuError> {
+        DmgPalette::parse(&fs::read_to_string(path)?)
+    }
+}
+
+/// Parses four `RRGGBB` colors, optionally prefixed with `#` and separated by spaces or commas.
+fn parse_shades(value: &str) -> Option<Shades> {
+    let colors: Vec<Color> = value.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|color| !color.is_empty())
+        .map(|color| {
+            let hex = color.trim_start_matches('#');
+            if hex.len() != 6 {
+                return None;
+            }
+            u32::from_str_radix(hex, 16).ok().map(rgb)
+        })
+        .collect::<Option<_>>()?;
+
+    if colors.len() != 4 {
+        return None;
+    }
+    Some([colors[0], colors[1], colors[2], colors[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_found_by_name() {
+        assert_eq!(DmgPalette::preset("Pocket"), Some(DmgPalette::uniform(POCKET)));
+        assert_eq!(DmgPalette::preset("gray"), Some(DmgPalette::default()));
+        assert_eq!(DmgPalette::preset("sepia"), None);
+    }
+
+    #[test]
+    fn config_sets_separate_palettes() {
+        let palette = DmgPalette::parse("; comment\n\
+                                         preset = green\n\
+                                         obj0 = #FFFFFF #FF8484 #943A3A #000000\n\
+                                         \n\
+                                         obj1 = ffffff, 63a5ff, 0000ff, 000000\n")
+            .unwrap();
+
+        assert_eq!(palette.bg, GREEN);
+        assert_eq!(palette.obj0[1], rgb(0xFF8484));
+        assert_eq!(palette.obj1[2], rgb(0x0000FF));
+    }
+
+    #[test]
+    fn config_errors_report_the_line() {
+        let result = DmgPalette::parse("colors = FFFFFF C0C0C0 606060\n");
+        match result {
+            Err(EmuError::InvalidPalette { line: 1, .. }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        let result = DmgPalette::parse("bg = FFFFFF C0C0C0 606060 000000\nbgp = 1\n");
+        match result {
+            Err(EmuError::InvalidPalette { line: 2, .. }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
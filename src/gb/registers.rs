@@ -41,6 +41,12 @@ pub struct Registers {
     pub pc: u16,
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Registers {
     pub fn new() -> Registers {
         Registers {
@@ -109,7 +115,7 @@ impl Registers {
     }
 
     pub fn check(&self, f: Flags) -> bool {
-        return (self.f & (f as u8)) > 0;
+        (self.f & (f as u8)) > 0
     }
 
     pub fn clear(&mut self, f: Flags) {
@@ -1,65 +1,618 @@
 use gb::catridge::Cartrige;
 use gb::registers::Registers;
 use gb::cpu::Cpu;
-use gb::mmu::Mmu;
-use gb::mmu::MmuRead;
-use gb::gpu::Gpu;
-use gb::interrupts::Interrupts;
+use gb::bus::*;
+use gb::gpu::{Color, Renderer};
 use gb::component::SystemComponent;
+use gb::error::EmuError;
 use gb::display::*;
-use gb::input::Input;
+use gb::input::*;
+use gb::palette::DmgPalette;
 
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Number of clock ticks it takes the GPU to draw one full frame (154 lines of 456 ticks).
+pub const TICKS_PER_FRAME: i32 = 70224;
 
 pub struct System {
-    registers: Rc<RefCell<Registers>>,
     cpu: Cpu,
-    mmu: Rc<RefCell<Mmu>>,
-    gpu: Rc<RefCell<Gpu>>,
-    int: Rc<RefCell<Interrupts>>,
-    input: Rc<RefCell<Input>>,
+    bus: Bus,
+    joypad_source: Option<Box<dyn JoypadSource + Send>>,
+    frame_ready: bool,
+    /// Where the battery backed ram is saved, see `load_save`.
+    save_path: Option<PathBuf>,
 }
 
 impl System {
-    pub fn new(cart: Cartrige, input: Input) -> System {
-        let gpu = Rc::new(RefCell::new(Gpu::new()));
-        let regs = Rc::new(RefCell::new(Registers::new()));
-        let cart = Rc::new(cart);
-        let input = Rc::new(RefCell::new(input));
-
-        let mmu = Mmu::new(cart.clone(), gpu.clone(), input.clone());
-        let mmu = Rc::new(RefCell::new(mmu));
-        gpu.borrow_mut().mmu = Some(mmu.clone());
-
-        let int = Interrupts::new(mmu.clone(), regs.clone(), gpu.clone());
-        let int = Rc::new(RefCell::new(int));
-
-        let cpu = Cpu::new(regs.clone(), mmu.clone(), int.clone());
-
-        System {
-            cpu: cpu,
-            registers: regs,
-            mmu: mmu,
-            gpu: gpu,
-            int: int,
-            input: input,
-        }
-    }
-
-    #[allow(while_true)]
-    pub fn run(&mut self, mut dis: &mut SdlDisplay) {
-        self.mmu.borrow_mut().reset();
-        self.gpu.borrow_mut().reset();
-        while true {
-            let pc = self.registers.borrow().pc;
-            let instruction = self.mmu.borrow().read_u8(self.registers.borrow().pc);
-            self.registers.borrow_mut().pc = pc + 1;
-            let ticks = self.cpu.execute(instruction);
-            self.gpu.borrow_mut().step(ticks);
-            let int_ticks = self.int.borrow_mut().step(&mut dis);
-            self.cpu.ticks += int_ticks;
-            self.input.borrow_mut().step();
-        }
-    }
-}
\ No newline at end of file
+    pub fn new(cart: Cartrige) -> Result<System, EmuError> {
+        let mut bus = Bus::new(cart)?;
+        bus.reset();
+
+        Ok(System {
+            cpu: Cpu::new(),
+            bus,
+            joypad_source: None,
+            frame_ready: false,
+            save_path: None,
+        })
+    }
+
+    /// Executes a single instruction (and services pending interrupts afterwards).
+    /// Returns the number of clock ticks that were spent.
+    ///
+    /// Fetching an illegal opcode locks the cpu up and reports an error once. Stepping
+    /// a locked, halted or stopped system is still possible, time passes but no
+    /// instructions are executed.
+    pub fn step_instruction(&mut self) -> Result<i32, EmuError> {
+        if self.cpu.stopped() {
+            // the system clock is halted, only a button press brings the cpu back
+            if self.bus.joypad().any_pressed() {
+                self.cpu.wake();
+            }
+            return Ok(M_CYCLE);
+        }
+
+        let start = self.bus.cycles();
+        let result = self.cpu.step(&mut self.bus);
+        if self.bus.take_frame() {
+            self.frame_ready = true;
+        }
+
+        let ticks = (self.bus.cycles() - start) as i32;
+        result?;
+        if self.bus.take_save_due() {
+            self.flush_save()?;
+        }
+        Ok(ticks)
+    }
+
+    /// Runs instructions until at least `ticks` clock ticks have elapsed.
+    /// Returns the number of ticks that were actually spent, which can overshoot
+    /// `ticks` by up to one instruction.
+    pub fn run_cycles(&mut self, ticks: i32) -> Result<i32, EmuError> {
+        let mut elapsed = 0;
+        while elapsed < ticks {
+            elapsed += self.step_instruction()?;
+        }
+        Ok(elapsed)
+    }
+
+    /// Runs until the GPU finished drawing the next frame, but never longer than one
+    /// frame worth of ticks (twice as many in CGB double speed mode). Returns the number
+    /// of ticks that were spent.
+    pub fn run_frame(&mut self) -> Result<i32, EmuError> {
+        if let Some(ref mut source) = self.joypad_source {
+            let state = source.poll();
+            self.bus.set_joypad(state);
+        }
+
+        self.frame_ready = false;
+        let limit = if self.bus.double_speed() {
+            TICKS_PER_FRAME * 2
+        } else {
+            TICKS_PER_FRAME
+        };
+        let mut elapsed = 0;
+        while !self.frame_ready && elapsed < limit {
+            elapsed += self.step_instruction()?;
+        }
+        Ok(elapsed)
+    }
+
+    /// True if a frame was completed since the last call to `run_frame` or `present`.
+    pub fn frame_ready(&self) -> bool {
+        self.frame_ready
+    }
+
+    pub fn framebuffer(&self) -> &[Color; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.bus.gpu().framebuffer
+    }
+
+    /// Switches between the accurate pixel FIFO and the faster scanline renderer.
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.bus.gpu_mut().set_renderer(renderer);
+    }
+
+    /// Makes VRAM and OAM inaccessible to the cpu while the GPU uses them, like on hardware.
+    pub fn set_access_locks(&mut self, enabled: bool) {
+        self.bus.gpu_mut().set_access_locks(enabled);
+    }
+
+    /// Changes the colors of the screen, see `DmgPalette` for the presets.
+    pub fn set_palette(&mut self, palette: DmgPalette) {
+        self.bus.gpu_mut().set_palette(palette);
+    }
+
+    /// Drives the cartridge's real-time clock from the host's clock instead of the
+    /// emulated cycles.
+    pub fn use_host_clock(&mut self, enabled: bool) {
+        self.bus.use_host_clock(enabled);
+    }
+
+    /// Sets the callback that is told when the cartridge's rumble motor turns on or off.
+    pub fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+        self.bus.set_rumble_callback(callback);
+    }
+
+    /// Returns the bytes sent over the serial port since the last call.
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        self.bus.take_serial_output()
+    }
+
+    /// Loads the cartridge ram from the save file at `path` if the cartridge has a
+    /// battery, a missing file is fine. From then on the ram is written back to `path`
+    /// a second after the game stops writing to it and by `flush_save`.
+    pub fn load_save(&mut self, path: &Path) -> Result<(), EmuError> {
+        if !self.bus.has_battery() {
+            return Ok(());
+        }
+        match fs::read(path) {
+            Ok(data) => self.bus.load_save_data(&data),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.save_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Writes the cartridge ram to the save file, frontends should call it on exit.
+    pub fn flush_save(&mut self) -> Result<(), EmuError> {
+        let path = match self.save_path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        // write a new file first, so a crash while saving doesn't destroy the old save
+        let temp = path.with_extension("sav.tmp");
+        fs::write(&temp, self.bus.save_data())?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Hands the current frame to `display`.
+    pub fn present(&mut self, display: &mut dyn Display) {
+        display.draw(&self.bus.gpu().framebuffer);
+        self.frame_ready = false;
+    }
+
+    /// Sets the joypad to `state`. Overridden at the next frame if a joypad source is attached.
+    pub fn set_joypad(&mut self, state: JoypadState) {
+        self.bus.set_joypad(state);
+    }
+
+    pub fn joypad(&self) -> JoypadState {
+        self.bus.joypad()
+    }
+
+    pub fn set_key(&mut self, key: Key, pressed: bool) {
+        let mut state = self.joypad();
+        state.set_key(key, pressed);
+        self.set_joypad(state);
+    }
+
+    /// Attaches a source that is polled for button state at the start of every frame.
+    /// Sources have to be `Send` so the whole system can be moved to another thread.
+    pub fn set_joypad_source(&mut self, source: Box<dyn JoypadSource + Send>) {
+        self.joypad_source = Some(source);
+    }
+
+    pub fn registers(&self) -> &Registers {
+        self.cpu.registers()
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        self.cpu.registers_mut()
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gb::catridge::CartridgeType;
+    use gb::interrupts::{INTERRUPT_ENABLE, INTERRUPT_FLAG, TIMER, VBLANK};
+    use gb::registers::{Flags, Reg16};
+
+    fn system_with_code(code: &[u8]) -> System {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x100 + code.len()].copy_from_slice(code);
+        System::new(Cartrige::from_bytes(rom).unwrap()).unwrap()
+    }
+
+    fn spinning_system() -> System {
+        // jr -2 at the entry point keeps the cpu spinning in place
+        let mut rom = vec![0; 0x8000];
+        rom[0x100] = 0x18;
+        rom[0x101] = 0xFE;
+        System::new(Cartrige::from_bytes(rom).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn serial_output_is_buffered() {
+        let mut system = spinning_system();
+        for &byte in b"ok" {
+            system.bus_mut().write_u8(0xFF01, byte);
+            system.bus_mut().write_u8(0xFF02, 0x81);
+        }
+        system.bus_mut().write_u8(0xFF02, 0x01);
+
+        assert_eq!(system.take_serial_output(), b"ok");
+        assert!(system.take_serial_output().is_empty());
+    }
+
+    #[test]
+    fn battery_ram_is_saved_after_writes_stop() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100] = 0x18;
+        rom[0x101] = 0xFE;
+        // MBC1 with 8 KB of battery backed ram
+        rom[0x147] = 0x03;
+        rom[0x149] = 0x02;
+        let path = std::env::temp_dir().join(format!("rsgb-{}.sav", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut system = System::new(Cartrige::from_bytes(rom.clone()).unwrap()).unwrap();
+        system.load_save(&path).unwrap();
+        // the ram is still disabled, so the write is dropped and nothing is saved
+        system.bus_mut().write_u8(0xA123, 0x5A);
+        system.run_cycles(2 * SAVE_DELAY).unwrap();
+        assert!(!path.exists());
+
+        system.bus_mut().write_u8(0x0000, 0x0A);
+        system.bus_mut().write_u8(0xA123, 0x5A);
+        system.run_cycles(SAVE_DELAY / 2).unwrap();
+        assert!(!path.exists());
+        system.run_cycles(SAVE_DELAY).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), 0x2000);
+
+        let mut loaded = System::new(Cartrige::from_bytes(rom).unwrap()).unwrap();
+        loaded.load_save(&path).unwrap();
+        loaded.bus_mut().write_u8(0x0000, 0x0A);
+        assert_eq!(loaded.bus().read_u8(0xA123), 0x5A);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn system_runs_headless() {
+        let mut system = spinning_system();
+        let mut display = FrameBuffer::new();
+
+        assert!(system.step_instruction().unwrap() > 0);
+        assert_eq!(system.registers().pc, 0x100);
+        system.run_frame().unwrap();
+        assert!(system.frame_ready());
+        assert_eq!(system.registers().pc, 0x100);
+
+        system.present(&mut display);
+        assert!(!system.frame_ready());
+        assert_eq!(display.frames, 1);
+        assert_eq!(display.pixels.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn joypad_source_is_polled_every_frame() {
+        let mut system = spinning_system();
+        let mut start = JoypadState::new();
+        start.start = true;
+        system.set_joypad_source(Box::new(Replay::new(vec![start, JoypadState::new()])));
+
+        system.run_frame().unwrap();
+        assert!(system.joypad().start);
+        let flags = system.bus().read_u8(0xFF0F);
+        assert!(flags & ::gb::interrupts::JOYPAD != 0);
+
+        // select the button keys, start is bit 3 and active low
+        system.bus_mut().write_u8(0xFF00, 0x10);
+        assert_eq!(system.bus().read_u8(0xFF00) & 0x0F, 0x07);
+
+        system.run_frame().unwrap();
+        assert!(!system.joypad().start);
+        assert_eq!(system.bus().read_u8(0xFF00) & 0x0F, 0x0F);
+    }
+
+    #[test]
+    fn illegal_opcode_locks_up_the_cpu() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100] = 0xD3;
+        let mut system = System::new(Cartrige::from_bytes(rom).unwrap()).unwrap();
+
+        match system.step_instruction() {
+            Err(EmuError::UnknownOpcode { opcode, pc }) => {
+                assert_eq!(opcode, 0xD3);
+                assert_eq!(pc, 0x100);
+            }
+            other => panic!("expected an unknown opcode error, got {:?}", other),
+        }
+
+        // the machine keeps running, but the cpu doesn't fetch anymore
+        assert!(system.run_frame().is_ok());
+        assert!(system.frame_ready());
+        assert_eq!(system.registers().pc, 0x101);
+    }
+
+    #[test]
+    fn unsupported_mapper_is_an_error() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x04;
+        match System::new(Cartrige::from_bytes(rom).unwrap()) {
+            Err(EmuError::UnsupportedMapper(CartridgeType::Unknown(0x04))) => {}
+            _ => panic!("expected an unsupported mapper error"),
+        }
+    }
+
+    #[test]
+    fn arithmetic_wraps_around() {
+        let mut system = system_with_code(&[0x01, 0x00, 0x00, // ld bc, 0
+                                            0x0B, // dec bc
+                                            0x21, 0x00, 0x00, // ld hl, 0
+                                            0x3A, // ldd a, (hl)
+                                            0x21, 0xFF, 0xFF, // ld hl, 0xFFFF
+                                            0x22, // ldi (hl), a
+                                            0xAF, // xor a
+                                            0x3D, // dec a
+                                            0x37, // scf
+                                            0xCE, 0xFF /* adc a, 0xFF */]);
+        for _ in 0..4 {
+            system.step_instruction().unwrap();
+        }
+        assert_eq!(system.registers().read_r16(Reg16::BC), 0xFFFF);
+        assert_eq!(system.registers().read_r16(Reg16::HL), 0xFFFF);
+        for _ in 0..4 {
+            system.step_instruction().unwrap();
+        }
+        assert_eq!(system.registers().read_r16(Reg16::HL), 0x0000);
+        assert_eq!(system.registers().a, 0xFF);
+
+        system.step_instruction().unwrap();
+        system.step_instruction().unwrap();
+        assert_eq!(system.registers().a, 0xFF);
+        assert!(system.registers().check(Flags::Carry));
+        assert!(system.registers().check(Flags::HalfCarry));
+        assert!(!system.registers().check(Flags::Negative));
+    }
+
+    #[test]
+    fn halt_idles_until_an_interrupt_is_pending() {
+        // di, halt, inc a
+        let mut system = system_with_code(&[0xF3, 0x76, 0x3C]);
+        system.step_instruction().unwrap();
+        system.step_instruction().unwrap();
+        for _ in 0..100 {
+            assert_eq!(system.step_instruction().unwrap(), 4);
+        }
+        assert_eq!(system.registers().pc, 0x102);
+
+        // IME is off, so the cpu wakes up without jumping to the handler
+        system.bus_mut().write_u8(INTERRUPT_ENABLE, TIMER);
+        system.bus_mut().write_u8(INTERRUPT_FLAG, TIMER);
+        system.step_instruction().unwrap();
+        system.step_instruction().unwrap();
+        assert_eq!(system.registers().pc, 0x103);
+        assert_eq!(system.registers().a, 0x02);
+    }
+
+    #[test]
+    fn halt_services_an_interrupt_that_is_already_pending() {
+        // di, ei, halt with the timer interrupt pending, the handler at 0x50 is a nop
+        let mut system = system_with_code(&[0xF3, 0xFB, 0x76]);
+        system.bus_mut().write_u8(0xFF07, 0x00);
+        system.bus_mut().write_u8(INTERRUPT_ENABLE, TIMER);
+        system.bus_mut().write_u8(INTERRUPT_FLAG, TIMER);
+        for _ in 0..3 {
+            system.step_instruction().unwrap();
+        }
+        assert_eq!(system.registers().pc, 0x50);
+
+        system.step_instruction().unwrap();
+        assert_eq!(system.registers().pc, 0x51);
+    }
+
+    #[test]
+    fn halt_bug_reads_the_next_byte_twice() {
+        // di, halt, inc a with an interrupt already pending
+        let mut system = system_with_code(&[0xF3, 0x76, 0x3C]);
+        system.bus_mut().write_u8(INTERRUPT_ENABLE, TIMER);
+        system.bus_mut().write_u8(INTERRUPT_FLAG, TIMER);
+        for _ in 0..4 {
+            system.step_instruction().unwrap();
+        }
+        assert_eq!(system.registers().pc, 0x103);
+        assert_eq!(system.registers().a, 0x03);
+    }
+
+    #[test]
+    fn stop_waits_for_a_button_press() {
+        // stop, inc a
+        let mut system = system_with_code(&[0x10, 0x00, 0x3C]);
+        system.step_instruction().unwrap();
+        assert_eq!(system.bus().read_u8(0xFF04), 0);
+        system.run_frame().unwrap();
+        assert!(!system.frame_ready());
+        assert_eq!(system.registers().pc, 0x102);
+
+        system.set_key(Key::A, true);
+        system.step_instruction().unwrap();
+        system.step_instruction().unwrap();
+        assert_eq!(system.registers().pc, 0x103);
+        assert_eq!(system.registers().a, 0x02);
+    }
+
+    fn request(system: &mut System, enable: u8, flag: u8) {
+        system.bus_mut().write_u8(INTERRUPT_ENABLE, enable);
+        system.bus_mut().write_u8(INTERRUPT_FLAG, flag);
+    }
+
+    #[test]
+    fn ei_takes_effect_after_the_next_instruction() {
+        // di, ei, inc a, inc a
+        let mut system = system_with_code(&[0xF3, 0xFB, 0x3C, 0x3C]);
+        request(&mut system, TIMER, TIMER);
+        system.step_instruction().unwrap();
+        assert_eq!(system.step_instruction().unwrap(), 4);
+        assert_eq!(system.registers().pc, 0x102);
+
+        // inc a runs before the interrupt is dispatched
+        assert_eq!(system.step_instruction().unwrap(), 4 + 20);
+        assert_eq!(system.registers().a, 0x02);
+        assert_eq!(system.registers().pc, 0x50);
+        assert_eq!(system.bus().read_u8(INTERRUPT_FLAG) & TIMER, 0);
+    }
+
+    #[test]
+    fn di_right_after_ei_keeps_interrupts_disabled() {
+        // di, ei, di, inc a
+        let mut system = system_with_code(&[0xF3, 0xFB, 0xF3, 0x3C]);
+        request(&mut system, TIMER, TIMER);
+        for _ in 0..4 {
+            system.step_instruction().unwrap();
+        }
+        assert_eq!(system.registers().pc, 0x104);
+    }
+
+    #[test]
+    fn only_the_highest_priority_interrupt_is_dispatched() {
+        let mut system = system_with_code(&[0x00]);
+        request(&mut system, VBLANK | TIMER, VBLANK | TIMER);
+        system.step_instruction().unwrap();
+        assert_eq!(system.registers().pc, 0x40);
+        assert_eq!(system.bus().read_u8(INTERRUPT_FLAG) & 0x1F, TIMER);
+    }
+
+    #[test]
+    fn pushing_pc_into_ie_can_cancel_the_dispatch() {
+        // ld sp, 0x0000; nop. The upper byte of pc (0x01) is pushed to 0xFFFF
+        // which disables the timer interrupt before it is chosen.
+        let mut system = system_with_code(&[0x31, 0x00, 0x00, 0x00]);
+        system.step_instruction().unwrap();
+        request(&mut system, TIMER, TIMER);
+        system.step_instruction().unwrap();
+
+        assert_eq!(system.registers().pc, 0x0000);
+        assert_eq!(system.registers().sp, 0xFFFE);
+        assert_eq!(system.bus().read_u8(INTERRUPT_ENABLE), 0x01);
+        assert_eq!(system.bus().read_u8(INTERRUPT_FLAG) & TIMER, TIMER);
+    }
+
+    #[test]
+    fn memory_accesses_happen_partway_through_an_instruction() {
+        // nop; nop; ldh a, (TIMA)
+        let mut system = system_with_code(&[0x00, 0x00, 0xF0, 0x05]);
+        system.bus_mut().write_u8(0xFF04, 0x00);
+        system.bus_mut().write_u8(0xFF07, 0x05);
+        system.bus_mut().write_u8(0xFF05, 0x00);
+        for _ in 0..3 {
+            system.step_instruction().unwrap();
+        }
+
+        // TIMA ticks every 16 clock ticks, the read happens 20 ticks after DIV was reset
+        assert_eq!(system.registers().a, 0x01);
+    }
+
+    /// Resets DIV and starts TIMA counting every 16 clock ticks, so it increments in
+    /// every 4th m-cycle.
+    fn start_timer(system: &mut System) {
+        system.bus_mut().write_u8(0xFF04, 0x00);
+        system.bus_mut().write_u8(0xFF07, 0x05);
+        system.bus_mut().write_u8(0xFF05, 0x00);
+    }
+
+    #[test]
+    fn push_writes_after_an_internal_cycle() {
+        // ld sp, TAC; nop; push bc with c = 0x10; ldh a, (TIMA)
+        let mut system = system_with_code(&[0x31, 0x07, 0xFF, 0x00, 0xC5, 0xF0, 0x05]);
+        system.registers_mut().write_r16(Reg16::BC, 0x0010);
+        start_timer(&mut system);
+        for _ in 0..4 {
+            system.step_instruction().unwrap();
+        }
+
+        // TIMA is written in the 8th m-cycle, right after it incremented, and isn't
+        // incremented again before it's read
+        assert_eq!(system.registers().a, 0x10);
+    }
+
+    #[test]
+    fn conditional_return_checks_the_condition_first() {
+        // ld sp, TIMA; nop; nop; ret z
+        let mut system = system_with_code(&[0x31, 0x05, 0xFF, 0x00, 0x00, 0xC8]);
+        system.registers_mut().set(Flags::Zero);
+        start_timer(&mut system);
+        for _ in 0..4 {
+            system.step_instruction().unwrap();
+        }
+
+        // the low byte of pc is read from TIMA in the 8th m-cycle
+        assert_eq!(system.registers().pc, 0x0002);
+    }
+
+    fn wait_for_mode(system: &mut System, mode: u8) {
+        while system.bus().read_u8(0xFF41) & 0x03 != mode {
+            system.bus_mut().tick();
+        }
+    }
+
+    #[test]
+    fn vram_and_oam_are_locked_while_the_gpu_uses_them() {
+        let mut system = spinning_system();
+        system.set_access_locks(true);
+
+        wait_for_mode(&mut system, 2);
+        system.bus_mut().write_u8(0x8000, 0x12);
+        system.bus_mut().write_u8(0xFE00, 0x34);
+        assert_eq!(system.bus().read_u8(0xFE00), 0xFF);
+        assert_eq!(system.bus().read_u8(0x8000), 0x12);
+
+        wait_for_mode(&mut system, 3);
+        system.bus_mut().write_u8(0x8000, 0x56);
+        assert_eq!(system.bus().read_u8(0x8000), 0xFF);
+        assert_eq!(system.bus().read_u8(0xFE00), 0xFF);
+
+        wait_for_mode(&mut system, 0);
+        assert_eq!(system.bus().read_u8(0x8000), 0x12);
+        assert_eq!(system.bus().read_u8(0xFE00), 0x00);
+
+        system.set_access_locks(false);
+        wait_for_mode(&mut system, 3);
+        assert_eq!(system.bus().read_u8(0x8000), 0x12);
+    }
+
+    #[test]
+    fn oam_dma_copies_a_byte_per_cycle_and_only_leaves_hram_reachable() {
+        let mut system = spinning_system();
+        let bus = system.bus_mut();
+        for i in 0..0xA0 {
+            bus.write_u8(0xC000 + i, i as u8 + 1);
+        }
+        bus.write_u8(0xFF80, 0x42);
+
+        bus.write_u8(::gb::dma::DMA, 0xC0);
+        bus.tick();
+        assert_eq!(bus.read_u8(0xFE00), 0x00);
+        bus.tick();
+        assert_eq!(bus.read_u8(0xFE00), 0xFF);
+        assert_eq!(bus.read_u8(0x0100), 0x01);
+        assert_eq!(bus.read_u8(0xFF80), 0x42);
+
+        for _ in 0..159 {
+            bus.tick();
+        }
+        assert_eq!(bus.read_u8(0xFE00), 0x01);
+        assert_eq!(bus.read_u8(0xFE9F), 0xA0);
+        assert_eq!(bus.read_u8(0x0101), 0xFE);
+    }
+
+    #[test]
+    fn system_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<System>();
+    }
+}
@@ -0,0 +1,219 @@
+use gb::component::SystemComponent;
+
+pub const DIV: u16 = 0xFF04;
+pub const TIMA: u16 = 0xFF05;
+pub const TMA: u16 = 0xFF06;
+pub const TAC: u16 = 0xFF07;
+
+/// Internal divider value right after the DMG boot rom handed over to the cartridge.
+const DIV_AFTER_BOOT: u16 = 0xABCC;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum TimaState {
+    Counting,
+    /// TIMA overflowed and reads 0 for one m-cycle before it is reloaded from TMA.
+    Overflowed,
+    /// TIMA was just reloaded. Writes to TIMA are ignored during this m-cycle and
+    /// writes to TMA are copied into TIMA as well.
+    Reloading,
+}
+
+/// DIV/TIMA/TMA/TAC. TIMA counts falling edges of one bit of the 16-bit internal divider
+/// (selected by TAC) ANDed with the TAC enable bit, so writes to DIV and TAC can cause
+/// extra increments just like on the hardware.
+pub struct Timer {
+    div: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    state: TimaState,
+    remainder: i32,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            div: DIV_AFTER_BOOT,
+            tima: 0,
+            tma: 0,
+            tac: 0xF8,
+            state: TimaState::Counting,
+            remainder: 0,
+        }
+    }
+
+    /// Advances the timer by `ticks` clock ticks.
+    /// Returns true if the timer interrupt was requested.
+    pub fn step(&mut self, ticks: i32) -> bool {
+        let mut interrupt = false;
+        self.remainder += ticks;
+        while self.remainder >= 4 {
+            self.remainder -= 4;
+            interrupt |= self.tick();
+        }
+        interrupt
+    }
+
+    /// Advances the timer by one m-cycle (4 clock ticks).
+    fn tick(&mut self) -> bool {
+        let mut interrupt = false;
+        match self.state {
+            TimaState::Counting => {}
+            TimaState::Overflowed => {
+                self.tima = self.tma;
+                self.state = TimaState::Reloading;
+                interrupt = true;
+            }
+            TimaState::Reloading => self.state = TimaState::Counting,
+        }
+
+        let old = self.input();
+        self.div = self.div.wrapping_add(4);
+        if old && !self.input() {
+            self.increment();
+        }
+        interrupt
+    }
+
+    /// The signal TIMA is counting falling edges of.
+    fn input(&self) -> bool {
+        const DIVIDER_BITS: [u16; 4] = [9, 3, 5, 7];
+        let bit = DIVIDER_BITS[(self.tac & 0x03) as usize];
+        self.tac & 0x04 != 0 && (self.div >> bit) & 1 != 0
+    }
+
+    fn increment(&mut self) {
+        if self.tima == 0xFF {
+            self.tima = 0;
+            self.state = TimaState::Overflowed;
+        } else {
+            self.tima += 1;
+        }
+    }
+
+    pub fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            DIV => (self.div >> 8) as u8,
+            TIMA => self.tima,
+            TMA => self.tma,
+            TAC => self.tac | 0xF8,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_u8(&mut self, addr: u16, value: u8) {
+        let old = self.input();
+        match addr {
+            DIV => self.div = 0,
+            TIMA => {
+                match self.state {
+                    // writing during the overflow cycle cancels the reload and the interrupt
+                    TimaState::Overflowed => {
+                        self.tima = value;
+                        self.state = TimaState::Counting;
+                    }
+                    TimaState::Reloading => {}
+                    TimaState::Counting => self.tima = value,
+                }
+            }
+            TMA => {
+                self.tma = value;
+                if self.state == TimaState::Reloading {
+                    self.tima = value;
+                }
+            }
+            TAC => self.tac = value | 0xF8,
+            _ => {}
+        }
+        if old && !self.input() {
+            self.increment();
+        }
+    }
+}
+
+impl SystemComponent for Timer {
+    fn reset(&mut self) {
+        *self = Timer::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(tac: u8) -> Timer {
+        let mut timer = Timer::new();
+        timer.write_u8(DIV, 0);
+        timer.write_u8(TAC, tac);
+        timer.write_u8(TIMA, 0);
+        timer
+    }
+
+    #[test]
+    fn div_counts_every_256_ticks_and_resets_on_write() {
+        let mut timer = Timer::new();
+        timer.write_u8(DIV, 0x12);
+        assert_eq!(timer.read_u8(DIV), 0);
+        timer.step(255);
+        assert_eq!(timer.read_u8(DIV), 0);
+        timer.step(1);
+        assert_eq!(timer.read_u8(DIV), 1);
+    }
+
+    #[test]
+    fn tima_frequencies() {
+        for &(tac, period) in &[(0x04, 1024), (0x05, 16), (0x06, 64), (0x07, 256)] {
+            let mut timer = timer(tac);
+            timer.step(period * 10);
+            assert_eq!(timer.read_u8(TIMA), 10, "tac {:02X}", tac);
+        }
+    }
+
+    #[test]
+    fn overflow_reloads_tma_one_cycle_late() {
+        let mut timer = timer(0x05);
+        timer.write_u8(TMA, 0x42);
+        timer.write_u8(TIMA, 0xFF);
+
+        assert!(!timer.step(16));
+        assert_eq!(timer.read_u8(TIMA), 0x00);
+        assert!(timer.step(4));
+        assert_eq!(timer.read_u8(TIMA), 0x42);
+    }
+
+    #[test]
+    fn writing_tima_during_overflow_cancels_the_reload() {
+        let mut timer = timer(0x05);
+        timer.write_u8(TMA, 0x42);
+        timer.write_u8(TIMA, 0xFF);
+        timer.step(16);
+
+        timer.write_u8(TIMA, 0x10);
+        assert!(!timer.step(4));
+        assert_eq!(timer.read_u8(TIMA), 0x10);
+    }
+
+    #[test]
+    fn resetting_div_can_increment_tima() {
+        let mut timer = timer(0x05);
+        // bit 3 of the divider is set after 8 ticks, clearing it is a falling edge
+        timer.step(8);
+        assert_eq!(timer.read_u8(TIMA), 0);
+        timer.write_u8(DIV, 0);
+        assert_eq!(timer.read_u8(TIMA), 1);
+    }
+
+    #[test]
+    fn disabling_the_timer_can_increment_tima() {
+        let mut timer = timer(0x05);
+        timer.step(8);
+        timer.write_u8(TAC, 0x01);
+        assert_eq!(timer.read_u8(TIMA), 1);
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+#[cfg(feature = "sdl")]
+extern crate time;
+
+pub mod gb;
+
+#[cfg(feature = "sdl")]
+pub mod frontend;
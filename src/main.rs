@@ -1,17 +1,38 @@
-mod gb;
+extern crate rsgb;
+extern crate sdl2;
 
-use gb::catridge::Cartrige;
-use gb::system::System;
-use gb::display::*;
-use gb::input::*;
+use rsgb::gb::catridge::Cartrige;
+use rsgb::gb::system::System;
+use rsgb::gb::input::JoypadSource;
+use rsgb::gb::palette::DmgPalette;
+use rsgb::gb::error::EmuError;
+use rsgb::frontend::sdl::*;
 
 use std::env;
 use std::path::Path;
 
-extern crate sdl2;
-
 fn main() {
     let mut args: Vec<String> = env::args().collect();
+    // --viewers opens windows with the tiles, tile maps and OAM
+    let viewers = args.iter().any(|arg| arg == "--viewers");
+    // --host-clock lets the cartridge clock follow the real time
+    let host_clock = args.iter().any(|arg| arg == "--host-clock");
+    // --frametime prints the time between presented frames
+    let frame_time = args.iter().any(|arg| arg == "--frametime");
+    args.retain(|arg| arg != "--viewers" && arg != "--host-clock" && arg != "--frametime");
+    // --palette takes a preset (grey, green, pocket, light) or the path of a palette config
+    let palette = match args.iter().position(|arg| arg == "--palette") {
+        Some(index) if index + 1 < args.len() => {
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Some(value)
+        }
+        Some(_) => {
+            println!("--palette needs a preset name or the path of a palette config");
+            return;
+        }
+        None => None,
+    };
     // debugging
     args.push("./TestRoms/tetris.gb".to_string());
     if args.len() < 2 {
@@ -21,10 +42,64 @@ fn main() {
 
     let context = sdl2::init().unwrap();
     let mut display = SdlDisplay::new(context.clone());
-    let input = Input::new(context.clone());
 
     let path = Path::new(&args[1]);
-    let c = Cartrige::from_path(path).unwrap();
-    let mut system = System::new(c, input);
-    system.run(&mut display);
+    let cart = Cartrige::from_path(path)
+        .inspect(|cart| println!("Cartrige {:?}", cart.header.cartridge_type));
+    let mut system = match cart.and_then(System::new) {
+        Ok(system) => system,
+        Err(err) => {
+            println!("Failed to load {}: {}", path.display(), err);
+            return;
+        }
+    };
+    system.use_host_clock(host_clock);
+    if let Some(palette) = palette {
+        match load_palette(&palette) {
+            Ok(palette) => system.set_palette(palette),
+            Err(err) => {
+                println!("Failed to load {}: {}", palette, err);
+                return;
+            }
+        }
+    }
+    let save_path = path.with_extension("sav");
+    if let Err(err) = system.load_save(&save_path) {
+        println!("Failed to load {}: {}", save_path.display(), err);
+    }
+    // SDL has to be polled from the main thread, so the keyboard isn't attached to the system
+    let mut keyboard = SdlKeyboard::new(context.clone());
+    let mut debug_views = if viewers {
+        Some(DebugViews::new(&context))
+    } else {
+        None
+    };
+    while !keyboard.quit_requested() {
+        system.set_joypad(keyboard.poll());
+        if let Err(err) = system.run_frame() {
+            println!("{}", err);
+        }
+        let serial = system.take_serial_output();
+        if !serial.is_empty() {
+            print!("{}", String::from_utf8_lossy(&serial));
+        }
+        system.present(&mut display);
+        if let (true, Some(delta)) = (frame_time, display.frame_time()) {
+            println!("Frametime {}s", delta);
+        }
+        if let Some(ref mut views) = debug_views {
+            views.update(system.bus().gpu());
+        }
+    }
+    if let Err(err) = system.flush_save() {
+        println!("Failed to write {}: {}", save_path.display(), err);
+    }
+}
+
+/// Looks `value` up as a preset first, anything else is the path of a palette config.
+fn load_palette(value: &str) -> Result<DmgPalette, EmuError> {
+    match DmgPalette::preset(value) {
+        Some(palette) => Ok(palette),
+        None => DmgPalette::from_path(Path::new(value)),
+    }
 }